@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::thread;
+
+use crate::grid::{Grid,GenericGrid};
+use crate::error::Error;
+use crate::generator::{Registry,Parameters};
+use crate::manifest::Manifest;
+use crate::image::RasterGrid;
+use crate::seed::Seed;
+use crate::serialization::SerializableGrid;
+
+///One map of a batch
+#[derive(Debug,Clone,PartialEq)]
+pub struct BatchJob {
+    ///position in the batch, also names the directory of the map
+    pub index: usize,
+    pub seed: u64,
+    ///the parameters that vary between the maps of the batch, on top of the common ones
+    pub varied: BTreeMap<String,String>,
+}
+
+///Measurements of a generated map, for the summary of a batch
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Metrics {
+    ///fraction of the cells that are set (non-zero)
+    pub filled: f64,
+    ///number of regions of set cells, connected horizontally or vertically
+    pub regions: usize,
+    ///number of cells in the largest region
+    pub largestregion: usize,
+    ///set cells with exactly one set neighbour
+    pub deadends: usize,
+    pub min: u8,
+    pub max: u8,
+    pub mean: f64,
+}
+
+///The outcome of one map of a batch: its manifest and metrics, or why it could not be generated
+pub type BatchResult = (BatchJob, Result<(Manifest, Metrics), Error>);
+
+///A batch of maps of one type and size, over a range of seeds and a grid of parameter values
+pub struct Batch {
+    pub generator: String,
+    pub width: u16,
+    pub height: u16,
+    ///parameters shared by all maps
+    pub parameters: Parameters,
+    pub jobs: Vec<BatchJob>,
+    ///style and cell size of the image of every map
+    pub style: String,
+    pub cellsize: usize,
+}
+
+impl Batch {
+    ///A job for every seed with every combination of the varied parameter values
+    pub fn new(generator: &str, width: u16, height: u16, parameters: Parameters, seeds: &[u64], varied: &[(String, Vec<String>)]) -> Batch {
+        let mut combinations: Vec<BTreeMap<String,String>> = vec!(BTreeMap::new());
+        for (name, values) in varied {
+            combinations = combinations.iter().flat_map(|combination| values.iter().map(move |value| {
+                let mut combination = combination.clone();
+                combination.insert(name.clone(), value.clone());
+                combination
+            })).collect();
+        }
+        let jobs = combinations.iter().flat_map(|combination| seeds.iter().map(move |seed| (*seed, combination.clone())))
+            .enumerate()
+            .map(|(index, (seed, varied))| BatchJob { index, seed, varied })
+            .collect();
+        Batch {
+            generator: generator.to_string(),
+            width,
+            height,
+            parameters,
+            jobs,
+            style: "default".to_string(),
+            cellsize: 8,
+        }
+    }
+
+    ///Generates all maps on the given number of threads. Every map gets a directory under
+    ///`directory` with the map (`map.grid`), an image of it (`map.png`) and its manifest
+    ///(`manifest.toml`), `summary.csv` lists the metrics of all maps. Maps that can not be
+    ///generated are left out of the summary, the results (in the order of the jobs) tell why.
+    ///`progress` is called from the worker threads after every map.
+    pub fn run(&self, registry: &Registry, directory: &str, threads: usize, progress: &(dyn Fn(&BatchResult) + Sync)) -> Result<Vec<BatchResult>, Error> {
+        if registry.get(&self.generator).is_none() {
+            return Err(Error::InvalidParameter(format!("No such generator: {}", self.generator)));
+        }
+        fs::create_dir_all(directory)?;
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<BatchResult>> = Mutex::new(Vec::with_capacity(self.jobs.len()));
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| {
+                    while let Some(job) = self.jobs.get(next.fetch_add(1, Ordering::SeqCst)) {
+                        let result = (job.clone(), self.generate(registry, directory, job));
+                        progress(&result);
+                        results.lock().unwrap().push(result);
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(job, _)| job.index);
+        fs::write(Path::new(directory).join("summary.csv"), self.summary(&results))?;
+        Ok(results)
+    }
+
+    ///The name of the directory of a map
+    pub fn mapdirectory(&self, job: &BatchJob) -> String {
+        format!("{:05}-{}", job.index, job.seed)
+    }
+
+    fn generate(&self, registry: &Registry, directory: &str, job: &BatchJob) -> Result<(Manifest, Metrics), Error> {
+        let generator = registry.get(&self.generator).ok_or_else(|| Error::InvalidParameter(format!("No such generator: {}", self.generator)))?;
+        let mut parameters = self.parameters.clone();
+        parameters.extend(job.varied.iter().map(|(name, value)| (name.clone(), value.clone())));
+        let grid = generator.generate(self.width, self.height, job.seed, &parameters, &mut |_, _| {})?;
+        let manifest = Manifest::new(registry, &self.generator, &parameters, job.seed, &grid)?;
+        let mapdirectory = Path::new(directory).join(self.mapdirectory(job));
+        fs::create_dir_all(&mapdirectory)?;
+        grid.save(&mapdirectory.join("map.grid").to_string_lossy())?;
+        generator.render(&grid, &self.style, &parameters)?.rasterize(self.cellsize).save(&mapdirectory.join("map.png").to_string_lossy())?;
+        manifest.save(&mapdirectory.join("manifest.toml").to_string_lossy())?;
+        Ok((manifest, metrics(&grid)))
+    }
+
+    ///The summary as CSV: a row per map with its directory, seed, varied parameters, hash and metrics
+    pub fn summary(&self, results: &[BatchResult]) -> String {
+        let varied: Vec<&String> = self.jobs.first().map(|job| job.varied.keys().collect()).unwrap_or_default();
+        let mut header: Vec<&str> = vec!("index", "directory", "type", "width", "height", "seed");
+        header.extend(varied.iter().map(|name| name.as_str()));
+        header.extend(&["hash", "filled", "regions", "largestregion", "deadends", "min", "max", "mean"]);
+        let mut csv = header.join(",") + "\n";
+        for (job, result) in results {
+            if let Ok((manifest, metrics)) = result {
+                let mut row: Vec<String> = vec!(job.index.to_string(), self.mapdirectory(job), self.generator.clone(), self.width.to_string(), self.height.to_string(), job.seed.to_string());
+                row.extend(varied.iter().map(|name| job.varied.get(*name).cloned().unwrap_or_default()));
+                row.extend(vec!(manifest.hash.clone(), format!("{:.4}", metrics.filled), metrics.regions.to_string(), metrics.largestregion.to_string(),
+                                metrics.deadends.to_string(), metrics.min.to_string(), metrics.max.to_string(), format!("{:.4}", metrics.mean)));
+                csv += &row.iter().map(|field| csvfield(field)).collect::<Vec<String>>().join(",");
+                csv += "\n";
+            }
+        }
+        csv
+    }
+}
+
+///Parses a comma separated list of seeds and ranges of seeds (`1..100`, the end is excluded), seeds
+///that are not numbers are hashed like `Seed::parse()`
+pub fn seeds(text: &str) -> Result<Vec<u64>, Error> {
+    let mut seeds = Vec::new();
+    for item in text.split(',').map(|item| item.trim()) {
+        if let Some((start, end)) = item.split_once("..") {
+            let start: u64 = start.parse().map_err(|_| Error::Parse(format!("Invalid seed range: {}", item)))?;
+            let end: u64 = end.parse().map_err(|_| Error::Parse(format!("Invalid seed range: {}", item)))?;
+            seeds.extend(start..end);
+        } else if !item.is_empty() {
+            seeds.push(Seed::parse(item).0);
+        }
+    }
+    if seeds.is_empty() {
+        return Err(Error::Parse(format!("No seeds in {}", text)));
+    }
+    Ok(seeds)
+}
+
+///Measures a map, see `Metrics`
+pub fn metrics(grid: &Grid<u16,u8>) -> Metrics {
+    let width = grid.width_as_usize();
+    let height = grid.height_as_usize();
+    let data = grid.get_data_vec();
+    let neighbours = |index: usize| {
+        let (x, y) = (index % width, index / width);
+        let mut neighbours = Vec::with_capacity(4);
+        if x > 0 { neighbours.push(index - 1); }
+        if x + 1 < width { neighbours.push(index + 1); }
+        if y > 0 { neighbours.push(index - width); }
+        if y + 1 < height { neighbours.push(index + width); }
+        neighbours.into_iter().filter(|neighbour| data[*neighbour] != 0)
+    };
+    let mut metrics = Metrics {
+        min: data.iter().copied().min().unwrap_or(0),
+        max: data.iter().copied().max().unwrap_or(0),
+        ..Metrics::default()
+    };
+    if data.is_empty() {
+        return metrics;
+    }
+    metrics.filled = data.iter().filter(|value| **value != 0).count() as f64 / data.len() as f64;
+    metrics.mean = data.iter().map(|value| *value as f64).sum::<f64>() / data.len() as f64;
+    metrics.deadends = (0..data.len()).filter(|index| data[*index] != 0 && neighbours(*index).count() == 1).count();
+    //flood fill every region that has not been visited yet
+    let mut visited = vec!(false; data.len());
+    for start in 0..data.len() {
+        if data[start] == 0 || visited[start] {
+            continue;
+        }
+        metrics.regions += 1;
+        let mut size = 0;
+        let mut stack = vec!(start);
+        visited[start] = true;
+        while let Some(index) = stack.pop() {
+            size += 1;
+            for neighbour in neighbours(index) {
+                if !visited[neighbour] {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        metrics.largestregion = metrics.largestregion.max(size);
+    }
+    metrics
+}
+
+///Quotes a CSV field if needed
+fn csvfield(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,94 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::image::DEFAULT_FOREGROUND;
+
+///The colour a cell shows when packed into a half block: its background, or its foreground if the
+///cell is filled with a full block
+fn cellcolour(cell: &RenderedTextCell) -> Option<(u8,u8,u8)> {
+    if cell.text.as_deref() == Some("█") {
+        Some(cell.foreground_colour.unwrap_or(DEFAULT_FOREGROUND))
+    } else {
+        cell.background_colour
+    }
+}
+
+pub trait HalfBlockGrid<ScaleType> {
+    fn halfblocks(&self) -> Grid<ScaleType,RenderedTextCell>;
+}
+
+impl<ScaleType> HalfBlockGrid<ScaleType> for Grid<ScaleType,RenderedTextCell> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Packs every two vertically adjacent cells into a single upper half block (▀), with the colour
+    ///of the upper cell as foreground and the lower cell as background. As terminal characters are
+    ///about twice as tall as wide, the cells become square. Only colours are preserved, not text.
+    fn halfblocks(&self) -> Grid<ScaleType,RenderedTextCell> {
+        let height = self.height_as_usize().div_ceil(2);
+        let mut packed: Grid<ScaleType,RenderedTextCell> = Grid::new(self.width(), ScaleType::from_usize(height).unwrap());
+        for point in packed.rectangle().iter() {
+            let top = cellcolour(&self[&Point::new_usize(point.xs(), point.ys() * 2)]);
+            let bottom = self.get(&Point::new_usize(point.xs(), point.ys() * 2 + 1)).and_then(cellcolour);
+            //cells without colour are left to the terminal's default background
+            let cell = match (top, bottom) {
+                (Some(top), bottom) => RenderedTextCell { background_colour: bottom, foreground_colour: Some(top), text: Some("▀".to_string()) },
+                (None, Some(bottom)) => RenderedTextCell { background_colour: None, foreground_colour: Some(bottom), text: Some("▄".to_string()) },
+                (None, None) => RenderedTextCell::default(),
+            };
+            packed.set(&point, cell);
+        }
+        packed
+    }
+}
+
+///Bits of the dots in a braille pattern (U+2800 onwards) by their position in the 2x4 block
+const BRAILLEDOTS: [[u32;2];4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+pub trait BrailleGrid<ScaleType> {
+    fn braille(&self, colour: Option<(u8,u8,u8)>) -> Grid<ScaleType,RenderedTextCell>;
+}
+
+impl<ScaleType> BrailleGrid<ScaleType> for Grid<ScaleType,bool> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Packs every block of 2x4 cells into a single braille character, with a dot for every cell
+    ///that is set, drawn in the given foreground colour
+    fn braille(&self, colour: Option<(u8,u8,u8)>) -> Grid<ScaleType,RenderedTextCell> {
+        let width = self.width_as_usize().div_ceil(2);
+        let height = self.height_as_usize().div_ceil(4);
+        let mut packed: Grid<ScaleType,RenderedTextCell> = Grid::new(ScaleType::from_usize(width).unwrap(), ScaleType::from_usize(height).unwrap());
+        for point in packed.rectangle().iter() {
+            let mut pattern = 0;
+            for (dy, row) in BRAILLEDOTS.iter().enumerate() {
+                for (dx, bit) in row.iter().enumerate() {
+                    //get() does not check the bounds of each dimension, the right half of the last block may be outside
+                    let x = point.xs() * 2 + dx;
+                    if x < self.width_as_usize() && self.get(&Point::new_usize(x, point.ys() * 4 + dy)) == Some(&true) {
+                        pattern |= bit;
+                    }
+                }
+            }
+            packed.set(&point, RenderedTextCell {
+                background_colour: None,
+                foreground_colour: colour,
+                text: Some(std::char::from_u32(0x2800 + pattern).unwrap().to_string()),
+            });
+        }
+        packed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braille_odd_width() {
+        let mut grid: Grid<u16,bool> = Grid::new(3, 4);
+        grid.set(&Point(0, 1), true);
+        let packed = grid.braille(None);
+        let text: String = (0..2).map(|x| packed[&Point(x, 0)].text.clone().unwrap()).collect();
+        assert_eq!(text, "⠂⠀");
+    }
+}
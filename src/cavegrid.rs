@@ -0,0 +1,235 @@
+use rand::{SeedableRng,Rng};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+use std::cmp::{min,max,PartialEq};
+use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,CheckedAdd,CheckedSub};
+
+use crate::common::Direction;
+use crate::point::Point;
+use crate::rectangle::Rectangle;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter};
+
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+#[derive(Debug,Clone,Default)]
+pub struct CaveGridProperties {
+    ///number of walkers carving simultaneously, all start at the centre
+    pub walkers: usize,
+
+    ///stop when this fraction (0.0 - 1.0) of the grid has been carved out
+    pub floor: f64,
+
+    ///probability that a walker changes direction at each step
+    pub turnprobability: f64,
+
+    ///probability that a room is stamped out at a walker's position at each step
+    pub roomprobability: f64,
+
+    ///maximum width and height of stamped rooms
+    pub roomsize: usize,
+}
+
+pub trait CaveGrid<ScaleType, ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: CaveGridProperties) -> Grid<ScaleType,ValueType>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: CaveGridProperties) -> Grid<ScaleType,ValueType>;
+    fn render(&self) -> Grid<ScaleType,RenderedTextCell>;
+    fn rendercell(&self, point: &Point<ScaleType>) -> RenderedTextCell;
+}
+
+impl<ScaleType,ValueType> CaveGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    ///Carves out caves with random walkers (drunkard's walk), floor is set to one and rock is zero.
+    ///The outer border of the grid is never carved.
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: CaveGridProperties) -> Grid<ScaleType,ValueType> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties)
+    }
+
+    ///Generates caves like `generate()`, with a random number generator of the given type seeded from
+    ///`seed`
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: CaveGridProperties) -> Grid<ScaleType,ValueType> {
+        let mut rng = R::seed_from_u64(seed);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width, height);
+        if grid.width_as_usize() < 3 || grid.height_as_usize() < 3 {
+            return grid;
+        }
+        let interior: Rectangle<ScaleType> = Rectangle::new_dims(ScaleType::one(), ScaleType::one(), width - ScaleType::from_u8(2).unwrap(), height - ScaleType::from_u8(2).unwrap());
+        let goal = (properties.floor * ((grid.width_as_usize() - 2) * (grid.height_as_usize() - 2)) as f64) as usize;
+        //safety limit, in case the goal can not be reached
+        let maxsteps = 100 * grid.width_as_usize() * grid.height_as_usize();
+
+        let centre = Point::new_usize(grid.width_as_usize() / 2, grid.height_as_usize() / 2);
+        let mut walkers: Vec<(Point<ScaleType>, Direction)> = (0..max(properties.walkers,1)).map(|_| {
+            (centre, *DIRECTIONS.choose(&mut rng).unwrap())
+        }).collect();
+        grid.set(&centre, ValueType::one());
+        let mut floor = 1;
+
+        let mut steps = 0;
+        while floor < goal && steps < maxsteps {
+            for (point, direction) in walkers.iter_mut() {
+                if rng.gen::<f64>() < properties.turnprobability {
+                    *direction = *DIRECTIONS.choose(&mut rng).unwrap();
+                }
+                match point.neighbour(*direction, Some(width), Some(height)) {
+                    Some(next) if next.x() >= interior.left() && next.x() <= interior.right() && next.y() >= interior.top() && next.y() <= interior.bottom() => {
+                        *point = next;
+                    },
+                    _ => {
+                        //bumped into the border, try another direction next step
+                        *direction = *DIRECTIONS.choose(&mut rng).unwrap();
+                        continue;
+                    }
+                }
+                if !grid.is_set(point) {
+                    grid.set(point, ValueType::one());
+                    floor += 1;
+                }
+                if properties.roomsize > 0 && rng.gen::<f64>() < properties.roomprobability {
+                    //stamp a room centered on the walker, clipped to the interior
+                    let roomwidth = rng.gen_range(1, properties.roomsize + 1);
+                    let roomheight = rng.gen_range(1, properties.roomsize + 1);
+                    let left = max(point.xs().saturating_sub(roomwidth / 2), interior.left().to_usize().unwrap());
+                    let top = max(point.ys().saturating_sub(roomheight / 2), interior.top().to_usize().unwrap());
+                    let right = min(left + roomwidth - 1, interior.right().to_usize().unwrap());
+                    let bottom = min(top + roomheight - 1, interior.bottom().to_usize().unwrap());
+                    let room: Rectangle<ScaleType> = Rectangle::new(&Point::new_usize(left, top), &Point::new_usize(right, bottom));
+                    for roompoint in room.iter() {
+                        if !grid.is_set(&roompoint) {
+                            grid.set(&roompoint, ValueType::one());
+                            floor += 1;
+                        }
+                    }
+                }
+            }
+            steps += 1;
+        }
+        grid
+    }
+
+    fn render(&self) -> Grid<ScaleType,RenderedTextCell> {
+        let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
+        for point in self.rectangle().iter() {
+            renderedgrid.set(&point,  CaveGrid::rendercell(self, &point) );
+        }
+        renderedgrid
+    }
+
+    fn rendercell(&self, point: &Point<ScaleType>) -> RenderedTextCell {
+        if self[point] != ValueType::zero() {
+            RenderedTextCell {
+                background_colour: Some((127,127,127)),
+                foreground_colour: None,
+                text: None
+            }
+        } else {
+            RenderedTextCell {
+                background_colour: Some((0,0,0)),
+                foreground_colour: None,
+                text: None
+            }
+        }
+    }
+}
+
+///Generates caves through `CaveGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct CaveGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for CaveGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = CaveGridProperties;
+
+    fn name(&self) -> &'static str {
+        "caves"
+    }
+
+    fn description(&self) -> &'static str {
+        "Twisty tunnels carved out by random walkers, with small rooms along the way"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("walkers", "Number of random walkers", Some("4")),
+            ParameterInfo::new("floor", "Fraction of the map (0.0-1.0) to carve out", Some("0.4")),
+            ParameterInfo::new("turn", "Probability (0.0-1.0) that a walker changes direction at each step", Some("0.3")),
+            ParameterInfo::new("roomchance", "Probability (0.0-1.0) of stamping a room at a walker's position at each step", Some("0.01")),
+            ParameterInfo::new("roomsize", "Maximum width/height of stamped rooms", Some("5")),
+        )
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<CaveGridProperties, Error> {
+        Ok(CaveGridProperties {
+            walkers: parameter(parameters, "walkers")?,
+            floor: parameter(parameters, "floor")?,
+            turnprobability: parameter(parameters, "turn")?,
+            roomprobability: parameter(parameters, "roomchance")?,
+            roomsize: parameter(parameters, "roomsize")?,
+        })
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &CaveGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as CaveGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone()))
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, _style: &str, _properties: &CaveGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        CaveGrid::render(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> CaveGridProperties {
+        CaveGridProperties {
+            walkers: 4,
+            floor: 0.4,
+            turnprobability: 0.3,
+            roomprobability: 0.05,
+            roomsize: 5,
+        }
+    }
+
+    fn generate(seed: u64) -> Grid<u16,u8> {
+        <Grid<u16,u8> as CaveGrid<u16,u8>>::generate(60, 30, seed, properties())
+    }
+
+    #[test]
+    fn same_seed_same_caves() {
+        assert!(generate(7) == generate(7));
+        assert!(generate(7) != generate(8));
+    }
+
+    #[test]
+    fn caves_are_connected() {
+        for seed in 1..6 {
+            let grid = generate(seed);
+            //all walkers start at the centre and rooms are stamped where a walker is, so the floor
+            //is a single region
+            let centre = Point(30, 15);
+            let mut reached: Grid<u16,bool> = Grid::new(60, 30);
+            let mut stack = vec![centre];
+            reached.set(&centre, true);
+            while let Some(point) = stack.pop() {
+                for neighbour in grid.getneighbours(&point) {
+                    if grid[&neighbour] != 0 && !reached[&neighbour] {
+                        reached.set(&neighbour, true);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            for (point, value) in grid.iter() {
+                assert!(*value == 0 || reached[&point], "seed {}: floor at {} is not connected to the centre", seed, point);
+            }
+        }
+    }
+}
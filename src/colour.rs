@@ -0,0 +1,157 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use ansi_term;
+use ansi_colours::{ansi256_from_rgb,rgb_from_ansi256};
+
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+
+///How colours are written to the terminal
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub enum ColourMode {
+    ///24-bit RGB colours
+    #[default]
+    TrueColour,
+    ///the 256-colour xterm palette
+    Ansi256,
+    ///the 16 basic ANSI colours
+    Ansi16,
+    ///no colours at all, only text (cells that only have a background colour are shaded)
+    None,
+}
+
+impl ColourMode {
+    ///Determines the colour mode from the environment: NO_COLOR disables colours, COLORTERM
+    ///announces true colour support and TERM may announce 256-colour support. Anything else gets
+    ///the 16 basic colours.
+    pub fn detect() -> ColourMode {
+        if env::var("NO_COLOR").is_ok_and(|value| !value.is_empty()) {
+            return ColourMode::None;
+        }
+        if env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit") {
+            return ColourMode::TrueColour;
+        }
+        match env::var("TERM") {
+            Ok(term) if term == "dumb" => ColourMode::None,
+            Ok(term) if term.contains("256") => ColourMode::Ansi256,
+            _ => ColourMode::Ansi16,
+        }
+    }
+
+    ///Writes text with the given foreground and background colours, approximating them as
+    ///closely as this colour mode allows
+    pub fn paint(&self, text: &str, foreground: Option<(u8,u8,u8)>, background: Option<(u8,u8,u8)>) -> String {
+        if foreground.is_none() && background.is_none() {
+            return text.to_string();
+        }
+        match self {
+            ColourMode::TrueColour | ColourMode::Ansi256 => {
+                let colour = |(r,g,b)| if *self == ColourMode::TrueColour {
+                    ansi_term::Colour::RGB(r,g,b)
+                } else {
+                    ansi_term::Colour::Fixed(ansi256_from_rgb((r,g,b)))
+                };
+                let mut style = ansi_term::Style::new();
+                if let Some(rgb) = background {
+                    style = style.on(colour(rgb));
+                }
+                if let Some(rgb) = foreground {
+                    style = style.fg(colour(rgb));
+                }
+                style.paint(text).to_string()
+            },
+            ColourMode::Ansi16 => {
+                //ansi_term has no bright background colours, so the escape codes are written directly
+                let mut codes: Vec<String> = Vec::new();
+                if let Some(rgb) = background {
+                    let index = ansi16_from_rgb(rgb);
+                    codes.push((if index < 8 { 40 + index } else { 100 + index - 8 }).to_string());
+                }
+                if let Some(rgb) = foreground {
+                    let index = ansi16_from_rgb(rgb);
+                    codes.push((if index < 8 { 30 + index } else { 90 + index - 8 }).to_string());
+                }
+                format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+            },
+            ColourMode::None => match background {
+                //without colours, a blank cell would be invisible, so it is shaded by its brightness instead
+                Some(rgb) if foreground.is_none() && text.trim().is_empty() => shade(rgb).to_string(),
+                _ => text.to_string(),
+            },
+        }
+    }
+}
+
+///Returns the closest of the 16 basic ANSI colours (0-7 normal, 8-15 bright)
+pub fn ansi16_from_rgb(rgb: (u8,u8,u8)) -> u8 {
+    let distance = |index: u8| {
+        let (r,g,b) = rgb_from_ansi256(index);
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+    };
+    (0..16).min_by_key(|index| distance(*index)).unwrap()
+}
+
+///Returns a shade glyph (space, light, medium, dark or full block) for the brightness of a colour
+pub fn shade(rgb: (u8,u8,u8)) -> char {
+    let brightness = (0.299 * rgb.0 as f64 + 0.587 * rgb.1 as f64 + 0.114 * rgb.2 as f64) / 255.0;
+    [' ', '░', '▒', '▓', '█'][(brightness * 4.0).round() as usize]
+}
+
+///Linear interpolation between two colours, with fraction 0.0 giving a and 1.0 giving b
+pub fn blend(a: (u8,u8,u8), b: (u8,u8,u8), fraction: f64) -> (u8,u8,u8) {
+    let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * fraction).round() as u8;
+    (mix(a.0,b.0), mix(a.1,b.1), mix(a.2,b.2))
+}
+
+impl FromStr for ColourMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truecolor" | "24bit" => Ok(ColourMode::TrueColour),
+            "256" => Ok(ColourMode::Ansi256),
+            "16" => Ok(ColourMode::Ansi16),
+            "none" => Ok(ColourMode::None),
+            _ => Err(format!("No such colour mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ColourMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            ColourMode::TrueColour => "truecolor",
+            ColourMode::Ansi256 => "256",
+            ColourMode::Ansi16 => "16",
+            ColourMode::None => "none",
+        })
+    }
+}
+
+impl RenderedTextCell {
+    pub fn paint(&self, mode: ColourMode) -> String {
+        mode.paint(self.text.as_deref().unwrap_or(" "), self.foreground_colour, self.background_colour)
+    }
+}
+
+pub trait PaintGrid<ScaleType> {
+    fn paint(&self, mode: ColourMode) -> String;
+}
+
+impl<ScaleType> PaintGrid<ScaleType> for Grid<ScaleType,RenderedTextCell> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Renders the grid for the terminal in the given colour mode, one line per row
+    fn paint(&self, mode: ColourMode) -> String {
+        let mut output = String::new();
+        for row in self.get_data_vec().chunks(self.width_as_usize().max(1)) {
+            for cell in row.iter() {
+                output += &cell.paint(mode);
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
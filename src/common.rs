@@ -1,6 +1,6 @@
 use num::{Integer,FromPrimitive,ToPrimitive};
 
-#[derive(Debug,Clone,Copy)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
 pub enum Direction {
     North,
     East,
@@ -8,6 +8,17 @@ pub enum Direction {
     West,
 }
 
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
 
 pub trait Distance {
     fn distance(&self, other: &Self) -> f64;
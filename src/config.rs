@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::Error;
+use crate::generator::{Registry,Parameters};
+
+///A preset of options and generator parameters, loaded from a TOML or JSON file. Keys at the top
+///level are general options (named like the options of the command line tool, e.g. `type`,
+///`style`, `width`, `color`) or parameters that apply to all generators, the tables in
+///`generators` hold the parameters of the generator with that name:
+///
+///```toml
+///type = "pipes"
+///style = "thick"
+///width = 120
+///
+///[generators.pipes]
+///backboneseeds = 40
+///regularseeds = [80, 80, 120]
+///interconnect = true
+///```
+///
+///Values are kept as text, like the parameters of generators, lists become comma separated.
+#[derive(Debug,Clone,Default,PartialEq)]
+pub struct Config {
+    pub options: BTreeMap<String,String>,
+    ///parameters by generator name
+    pub generators: BTreeMap<String,BTreeMap<String,String>>,
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    pub fn from_toml(text: &str) -> Result<Config, Error> {
+        let value: toml::Value = text.parse().map_err(|err| Error::Parse(format!("Invalid configuration: {}", err)))?;
+        let mut config = Config::new();
+        for (key, value) in value.as_table().into_iter().flatten() {
+            if key == "generators" {
+                for (generator, parameters) in value.as_table().ok_or_else(|| invalidvalue(key))? {
+                    let mut table = BTreeMap::new();
+                    for (name, value) in parameters.as_table().ok_or_else(|| invalidvalue(&format!("{}.{}", key, generator)))? {
+                        table.insert(name.clone(), tomltext(value).ok_or_else(|| invalidvalue(&format!("{}.{}.{}", key, generator, name)))?);
+                    }
+                    config.generators.insert(generator.clone(), table);
+                }
+            } else {
+                config.options.insert(key.clone(), tomltext(value).ok_or_else(|| invalidvalue(key))?);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn from_json(text: &str) -> Result<Config, Error> {
+        let value: serde_json::Value = serde_json::from_str(text).map_err(|err| Error::Parse(format!("Invalid configuration: {}", err)))?;
+        let mut config = Config::new();
+        for (key, value) in value.as_object().ok_or_else(|| Error::Parse("Invalid configuration: expected an object".to_string()))? {
+            if key == "generators" {
+                for (generator, parameters) in value.as_object().ok_or_else(|| invalidvalue(key))? {
+                    let mut table = BTreeMap::new();
+                    for (name, value) in parameters.as_object().ok_or_else(|| invalidvalue(&format!("{}.{}", key, generator)))? {
+                        table.insert(name.clone(), jsontext(value).ok_or_else(|| invalidvalue(&format!("{}.{}.{}", key, generator, name)))?);
+                    }
+                    config.generators.insert(generator.clone(), table);
+                }
+            } else {
+                config.options.insert(key.clone(), jsontext(value).ok_or_else(|| invalidvalue(key))?);
+            }
+        }
+        Ok(config)
+    }
+
+    ///Loads a configuration file, JSON if the file name ends in `.json`, TOML otherwise
+    pub fn load(filename: &str) -> Result<Config, Error> {
+        let text = fs::read_to_string(filename)?;
+        if filename.ends_with(".json") {
+            Config::from_json(&text)
+        } else {
+            Config::from_toml(&text)
+        }
+    }
+
+    ///Writes the configuration, as JSON if the file name ends in `.json`, TOML otherwise
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        if filename.ends_with(".json") {
+            fs::write(filename, self.to_json())?;
+        } else {
+            fs::write(filename, self.to_toml())?;
+        }
+        Ok(())
+    }
+
+    ///Values that look like numbers, booleans or lists of numbers are written as such
+    pub fn to_toml(&self) -> String {
+        let mut table = toml::value::Table::new();
+        for (key, value) in self.options.iter() {
+            table.insert(key.clone(), tomlvalue(value));
+        }
+        if !self.generators.is_empty() {
+            let generators: toml::value::Table = self.generators.iter().map(|(generator, parameters)| {
+                (generator.clone(), toml::Value::Table(parameters.iter().map(|(name, value)| (name.clone(), tomlvalue(value))).collect()))
+            }).collect();
+            table.insert("generators".to_string(), toml::Value::Table(generators));
+        }
+        toml::to_string(&toml::Value::Table(table)).expect("Unable to serialize configuration")
+    }
+
+    ///Values that look like numbers, booleans or lists of numbers are written as such
+    pub fn to_json(&self) -> String {
+        let mut object = serde_json::Map::new();
+        for (key, value) in self.options.iter() {
+            object.insert(key.clone(), jsonvalue(value));
+        }
+        if !self.generators.is_empty() {
+            let generators: serde_json::Map<String,serde_json::Value> = self.generators.iter().map(|(generator, parameters)| {
+                (generator.clone(), serde_json::Value::Object(parameters.iter().map(|(name, value)| (name.clone(), jsonvalue(value))).collect()))
+            }).collect();
+            object.insert("generators".to_string(), serde_json::Value::Object(generators));
+        }
+        serde_json::to_string_pretty(&serde_json::Value::Object(object)).expect("Unable to serialize configuration") + "\n"
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|value| value.as_str())
+    }
+
+    ///The value of a parameter for the given generator: from the table of the generator, or else
+    ///from the top level
+    pub fn parameter(&self, generator: &str, name: &str) -> Option<&str> {
+        self.generators.get(generator).and_then(|parameters| parameters.get(name)).map(|value| value.as_str()).or_else(|| self.option(name))
+    }
+
+    ///All parameters of the given generator that are set, see `parameter()`
+    pub fn parameters(&self, registry: &Registry, generator: &str) -> Parameters {
+        let mut parameters = Parameters::new();
+        if let Some(generator) = registry.get(generator) {
+            for info in generator.parameters() {
+                if let Some(value) = self.parameter(generator.name(), info.name) {
+                    parameters.insert(info.name.to_string(), value.to_string());
+                }
+            }
+        }
+        parameters
+    }
+
+    ///Checks for typing errors: the tables of generators must be named after a generator of the
+    ///registry and hold only its parameters, keys at the top level must be one of the given
+    ///options or a parameter of any generator
+    pub fn check(&self, registry: &Registry, options: &[&str]) -> Result<(), Error> {
+        for (key, parameters) in self.generators.iter() {
+            let generator = registry.get(key).ok_or_else(|| Error::InvalidParameter(format!("Invalid configuration: no such generator: {}", key)))?;
+            for name in parameters.keys() {
+                if !generator.parameters().iter().any(|info| info.name == name) {
+                    return Err(Error::InvalidParameter(format!("Invalid configuration: generator {} has no parameter {}", key, name)));
+                }
+            }
+        }
+        for key in self.options.keys() {
+            if !options.contains(&key.as_str()) && !registry.iter().any(|generator| generator.parameters().iter().any(|info| info.name == key)) {
+                return Err(Error::InvalidParameter(format!("Invalid configuration: unknown option {}", key)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalidvalue(key: &str) -> Error {
+    Error::Parse(format!("Invalid configuration: unsupported value for {}", key))
+}
+
+fn tomltext(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Float(f) => Some(f.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Array(values) => values.iter().map(tomltext).collect::<Option<Vec<String>>>().map(|values| values.join(",")),
+        _ => None,
+    }
+}
+
+fn jsontext(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        serde_json::Value::Array(values) => values.iter().map(jsontext).collect::<Option<Vec<String>>>().map(|values| values.join(",")),
+        _ => None,
+    }
+}
+
+fn tomlvalue(text: &str) -> toml::Value {
+    if let Ok(b) = text.parse::<bool>() {
+        toml::Value::Boolean(b)
+    } else if let Ok(i) = text.parse::<i64>() {
+        toml::Value::Integer(i)
+    } else if let Some(f) = text.parse::<f64>().ok().filter(|f| f.is_finite() && text.contains('.')) {
+        toml::Value::Float(f)
+    } else if text.contains(',') && text.split(',').all(|item| item.parse::<i64>().is_ok()) {
+        toml::Value::Array(text.split(',').map(|item| toml::Value::Integer(item.parse().unwrap())).collect())
+    } else {
+        toml::Value::String(text.to_string())
+    }
+}
+
+fn jsonvalue(text: &str) -> serde_json::Value {
+    match tomlvalue(text) {
+        toml::Value::Boolean(b) => serde_json::Value::from(b),
+        toml::Value::Integer(i) => serde_json::Value::from(i),
+        toml::Value::Float(f) => serde_json::Value::from(f),
+        toml::Value::Array(values) => serde_json::Value::Array(values.iter().filter_map(|value| value.as_integer()).map(serde_json::Value::from).collect()),
+        _ => serde_json::Value::from(text),
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io;
+
+///Errors of the fallible operations of the crate
+#[derive(Debug)]
+pub enum Error {
+    ///a coordinate or value does not fit in the scale or value type of the grid
+    OutOfBounds,
+    ///there is no room for what was requested, e.g. a random rectangle in bounds smaller than its minimum size
+    EmptyRange,
+    ///maps must be at least 1x1
+    InvalidDimensions(usize, usize),
+    ///a parameter of a generator is missing or has an invalid value
+    InvalidParameter(String),
+    ///input (e.g. a text map or character table) could not be parsed
+    Parse(String),
+    ///the generator gave up before the map was complete, e.g. wave function collapse after too many backtracks
+    Generation(String),
+    Io(io::Error),
+}
+
+///Result type of the fallible operations of the crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OutOfBounds => write!(f, "Out of bounds"),
+            Error::EmptyRange => write!(f, "Empty range, the map is too small"),
+            Error::InvalidDimensions(width, height) => write!(f, "Invalid map dimensions {}x{}", width, height),
+            Error::InvalidParameter(message) | Error::Parse(message) | Error::Generation(message) => write!(f, "{}", message),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,51 @@
+use num::{Integer,FromPrimitive,ToPrimitive};
+use std::fmt;
+
+use crate::common::Volume;
+use crate::point::Point;
+use crate::rectangle::Rectangle;
+use crate::grid::Grid;
+use crate::symmetry::Symmetry;
+
+///A step taken by a generator, reported to an observer along with the state of the map right after
+///the step. Coordinates are within the generated sector when symmetry is enforced.
+#[derive(Debug,Clone,PartialEq)]
+pub enum GenerationEvent<ScaleType> {
+    ///(pipes) a path of the backbone was drawn between two backbone seeds
+    BackbonePath(Point<ScaleType>, Point<ScaleType>),
+    ///(pipes) a regular seed of the given tier was placed and connected to the network by a path
+    ///from the seed to the target
+    Branch { seed: Point<ScaleType>, target: Point<ScaleType>, tier: usize },
+    ///(pipes) a dead end was connected to another part of the network
+    Interconnection(Point<ScaleType>, Point<ScaleType>),
+    ///(pipes) the given number of short dead-end branches were pruned
+    Pruned(usize),
+    ///(height) all cells in the rectangle were raised, except for the corners of larger rectangles
+    HeightRectangle(Rectangle<ScaleType>),
+    ///(rooms) a room was added
+    Room(Rectangle<ScaleType>),
+    ///(rooms) a corridor was drawn between two points
+    Corridor(Point<ScaleType>, Point<ScaleType>),
+    ///the generated sector was copied to the rest of the map
+    Symmetry(Symmetry),
+}
+
+///Receives every step of a generator with the map right after it
+pub type Observer<'a, ScaleType, ValueType> = dyn FnMut(&GenerationEvent<ScaleType>, &Grid<ScaleType,ValueType>) + 'a;
+
+impl<ScaleType> fmt::Display for GenerationEvent<ScaleType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationEvent::BackbonePath(from, to) => write!(f, "backbone path from {} to {}", from, to),
+            GenerationEvent::Branch { seed, target, tier } => write!(f, "seed at {}, tier {}, connected to {}", seed, tier, target),
+            GenerationEvent::Interconnection(from, to) => write!(f, "interconnection from {} to {}", from, to),
+            GenerationEvent::Pruned(count) => write!(f, "pruned {} branches", count),
+            GenerationEvent::HeightRectangle(rect) => write!(f, "raised {}x{} rectangle at {}", rect.width().to_usize().unwrap(), rect.height().to_usize().unwrap(), rect.topleft),
+            GenerationEvent::Room(rect) => write!(f, "room of {}x{} at {}", rect.width().to_usize().unwrap(), rect.height().to_usize().unwrap(), rect.topleft),
+            GenerationEvent::Corridor(from, to) => write!(f, "corridor from {} to {}", from, to),
+            GenerationEvent::Symmetry(symmetry) => write!(f, "{} symmetry applied", symmetry),
+        }
+    }
+}
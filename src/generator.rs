@@ -0,0 +1,242 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::events::Observer;
+use crate::error::Error;
+use crate::symmetry::Symmetry;
+use crate::svg::Svg;
+use crate::tiled::TiledMap;
+use crate::pipegrid::PipeGenerator;
+use crate::heightgrid::HeightGenerator;
+use crate::roomgrid::RoomGenerator;
+use crate::mazegrid::MazeGenerator;
+use crate::wfc::WfcGenerator;
+use crate::cavegrid::CaveGenerator;
+
+///Parameters of a generator by name, as text (e.g. from the command line or a configuration file)
+pub type Parameters = HashMap<String,String>;
+
+///Describes a parameter of a generator
+#[derive(Debug,Clone,PartialEq)]
+pub struct ParameterInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    ///the value used when the parameter is not given, parameters without a default are optional
+    pub default: Option<&'static str>,
+}
+
+impl ParameterInfo {
+    pub fn new(name: &'static str, description: &'static str, default: Option<&'static str>) -> ParameterInfo {
+        ParameterInfo { name, description, default }
+    }
+}
+
+///Parses a parameter, which must be present
+pub fn parameter<T: FromStr>(parameters: &Parameters, name: &str) -> Result<T, Error> {
+    optionalparameter(parameters, name)?.ok_or_else(|| Error::InvalidParameter(format!("Missing parameter: {}", name)))
+}
+
+///Parses a parameter, if present
+pub fn optionalparameter<T: FromStr>(parameters: &Parameters, name: &str) -> Result<Option<T>, Error> {
+    match parameters.get(name) {
+        Some(value) => value.parse::<T>().map(Some).map_err(|_| Error::InvalidParameter(format!("Invalid value for parameter {}: {}", name, value))),
+        None => Ok(None),
+    }
+}
+
+///Fails for maps smaller than 1x1
+pub(crate) fn checkdimensions(width: usize, height: usize) -> Result<(), Error> {
+    if width == 0 || height == 0 {
+        Err(Error::InvalidDimensions(width, height))
+    } else {
+        Ok(())
+    }
+}
+
+///Describes the size of a map for error messages, including the size of the sector that is
+///generated when symmetry is enforced
+pub(crate) fn describesize(width: usize, height: usize, symmetry: Symmetry) -> String {
+    if symmetry == Symmetry::None {
+        format!("{}x{} map", width, height)
+    } else {
+        let (sectorwidth, sectorheight) = symmetry.sector(width, height);
+        format!("{}x{} map ({}x{} with {} symmetry)", width, height, sectorwidth, sectorheight, symmetry)
+    }
+}
+
+///A map generator with everything needed to run it uniformly: its name, description and
+///parameters, building its properties from textual parameters, generation, rendering and the
+///optional exports. Generators are usually unit structs wrapping the generator traits of the
+///grid, like `PipeGenerator` for `PipeGrid`.
+pub trait MapGenerator<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy,
+    ValueType: ToPrimitive + Default + PartialEq + Clone {
+
+    type Properties;
+
+    ///the name to look the generator up by
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn parameters(&self) -> Vec<ParameterInfo>;
+
+    ///the names of the render styles, the first is the default
+    fn styles(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    ///Builds the properties from textual parameters, which include the defaults of all parameters
+    fn properties(&self, parameters: &Parameters) -> Result<Self::Properties, Error>;
+
+    ///Checks that the properties can be used for a map of the given size, generation is refused
+    ///if they can not
+    fn validate(&self, _width: ScaleType, _height: ScaleType, _properties: &Self::Properties) -> Result<(), Error> {
+        Ok(())
+    }
+
+    ///Generates the map, width and height are at least 1. Fails if the generator gives up before
+    ///the map is complete.
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &Self::Properties) -> Result<Grid<ScaleType,ValueType>, Error>;
+
+    ///Generates the map, reporting the steps to the observer. Generators that do not report steps
+    ///simply generate the map.
+    fn generate_with_events(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &Self::Properties, _observer: &mut Observer<ScaleType,ValueType>) -> Result<Grid<ScaleType,ValueType>, Error> {
+        self.generate(width, height, seed, properties)
+    }
+
+    ///Renders the map in the given style, unknown styles fall back to the default style
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, style: &str, properties: &Self::Properties) -> Grid<ScaleType,RenderedTextCell>;
+
+    fn svg(&self, _grid: &Grid<ScaleType,ValueType>, _cellsize: f64, _style: &str) -> Option<Svg> {
+        None
+    }
+
+    fn tiled(&self, _grid: &Grid<ScaleType,ValueType>, _tilesize: usize, _style: &str) -> Option<TiledMap> {
+        None
+    }
+
+    ///Describes what the value of a cell means
+    fn describe(&self, grid: &Grid<ScaleType,ValueType>, point: &Point<ScaleType>) -> String {
+        let value = grid.get(point).and_then(|value| value.to_i64()).unwrap_or(0);
+        format!("value {}", value)
+    }
+}
+
+///A map generator that can be stored in a `Registry`, all `MapGenerator`s for the grids of the
+///command line tool (`u16` coordinates, `u8` values) are one. Properties are built from textual
+///parameters, missing parameters get their defaults. Generation fails for maps smaller than 1x1,
+///for properties that do not fit the map and when the generator gives up. Generators are shared
+///between threads (see `batch`), so they must be `Send` and `Sync`.
+pub trait DynMapGenerator: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn parameters(&self) -> Vec<ParameterInfo>;
+    fn styles(&self) -> &'static [&'static str];
+    fn generate(&self, width: u16, height: u16, seed: u64, parameters: &Parameters, observer: &mut Observer<u16,u8>) -> Result<Grid<u16,u8>, Error>;
+    fn render(&self, grid: &Grid<u16,u8>, style: &str, parameters: &Parameters) -> Result<Grid<u16,RenderedTextCell>, Error>;
+    fn svg(&self, grid: &Grid<u16,u8>, cellsize: f64, style: &str) -> Option<Svg>;
+    fn tiled(&self, grid: &Grid<u16,u8>, tilesize: usize, style: &str) -> Option<TiledMap>;
+    fn describe(&self, grid: &Grid<u16,u8>, point: &Point<u16>) -> String;
+}
+
+impl<G> DynMapGenerator for G where G: MapGenerator<u16,u8> + Send + Sync {
+    fn name(&self) -> &'static str {
+        MapGenerator::name(self)
+    }
+
+    fn description(&self) -> &'static str {
+        MapGenerator::description(self)
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        MapGenerator::parameters(self)
+    }
+
+    fn styles(&self) -> &'static [&'static str] {
+        MapGenerator::styles(self)
+    }
+
+    fn generate(&self, width: u16, height: u16, seed: u64, parameters: &Parameters, observer: &mut Observer<u16,u8>) -> Result<Grid<u16,u8>, Error> {
+        checkdimensions(width as usize, height as usize)?;
+        let properties = self.properties(&withdefaults(parameters, &MapGenerator::parameters(self)))?;
+        MapGenerator::validate(self, width, height, &properties)?;
+        self.generate_with_events(width, height, seed, &properties, observer)
+    }
+
+    fn render(&self, grid: &Grid<u16,u8>, style: &str, parameters: &Parameters) -> Result<Grid<u16,RenderedTextCell>, Error> {
+        let properties = self.properties(&withdefaults(parameters, &MapGenerator::parameters(self)))?;
+        Ok(MapGenerator::render(self, grid, style, &properties))
+    }
+
+    fn svg(&self, grid: &Grid<u16,u8>, cellsize: f64, style: &str) -> Option<Svg> {
+        MapGenerator::svg(self, grid, cellsize, style)
+    }
+
+    fn tiled(&self, grid: &Grid<u16,u8>, tilesize: usize, style: &str) -> Option<TiledMap> {
+        MapGenerator::tiled(self, grid, tilesize, style)
+    }
+
+    fn describe(&self, grid: &Grid<u16,u8>, point: &Point<u16>) -> String {
+        MapGenerator::describe(self, grid, point)
+    }
+}
+
+///Adds the defaults of all parameters that are not given
+pub fn withdefaults(parameters: &Parameters, infos: &[ParameterInfo]) -> Parameters {
+    let mut parameters = parameters.clone();
+    for info in infos {
+        if let Some(default) = info.default {
+            parameters.entry(info.name.to_string()).or_insert_with(|| default.to_string());
+        }
+    }
+    parameters
+}
+
+///Generators by name, in the order they were registered
+pub struct Registry {
+    generators: Vec<Box<dyn DynMapGenerator>>,
+}
+
+impl Registry {
+    ///An empty registry, see `Registry::default()` for one with the built-in generators
+    pub fn new() -> Registry {
+        Registry {
+            generators: Vec::new(),
+        }
+    }
+
+    ///Adds a generator, replacing any generator with the same name
+    pub fn register(&mut self, generator: impl DynMapGenerator + 'static) {
+        let name = generator.name();
+        self.generators.retain(|other| other.name() != name);
+        self.generators.push(Box::new(generator));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn DynMapGenerator> {
+        self.generators.iter().find(|generator| generator.name() == name).map(|generator| generator.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&dyn DynMapGenerator> {
+        self.generators.iter().map(|generator| generator.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.generators.iter().map(|generator| generator.name()).collect()
+    }
+}
+
+///A registry with all built-in generators
+impl Default for Registry {
+    fn default() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(PipeGenerator);
+        registry.register(HeightGenerator);
+        registry.register(RoomGenerator);
+        registry.register(MazeGenerator);
+        registry.register(WfcGenerator);
+        registry.register(CaveGenerator);
+        registry
+    }
+}
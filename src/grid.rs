@@ -1,16 +1,15 @@
-use rand::{SeedableRng,Rng};
-use rand_pcg::Pcg32;
+use rand::Rng;
 use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,range,CheckedAdd,CheckedSub};
-use std::ops::{Index,Add,AddAssign};
-use std::cmp::{min,max,PartialEq,Eq,Ord,PartialOrd,Ordering};
+use std::ops::Index;
+use std::cmp::{min,max,Ord,PartialOrd,Ordering};
 use std::fmt;
-use std::iter::{Iterator,FromIterator};
-use std::collections::BinaryHeap;
-use ansi_term;
+use std::iter::Iterator;
 
 use crate::common::{Distance,Direction};
 use crate::point::Point;
 use crate::rectangle::{Rectangle,RectIterator};
+use crate::symmetry::Symmetry;
+use crate::colour::ColourMode;
 
 
 ///The basic grid type
@@ -33,6 +32,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
     ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy,
     ValueType: Default + PartialEq  + Clone {
 
+    #[allow(clippy::new_ret_no_self)]
     fn new(width: ScaleType, height: ScaleType) -> Grid<ScaleType,ValueType> {
         Self::new_init(width, height, ValueType::default())
     }
@@ -68,7 +68,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
 
     ///Point to Index
     fn index(&self, point: &Point<ScaleType>) -> usize {
-       (point.y() * self.width() + point.x()).to_usize().expect("Unable to cast to usize")
+       point.ys() * self.width_as_usize() + point.xs()
     }
 
     ///Index to Point
@@ -83,7 +83,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
             *v = value;
             return true;
         }
-        return false;
+        false
     }
 
     fn is_set(&self, point: &Point<ScaleType>) -> bool {
@@ -91,7 +91,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
     }
 
     fn set_index(&mut self, index: usize, value: ValueType) -> bool {
-        if let Some(mut v) = self.get_mut_by_index(index) {
+        if let Some(v) = self.get_mut_by_index(index) {
             *v = value;
             true
         } else {
@@ -101,10 +101,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
 
     fn hasneighbour(&self, point: &Point<ScaleType>, direction: Direction) -> bool {
         if let Some(neighbour) = point.neighbour(direction, Some(self.width()), Some(self.height())) {
-            match self.get(&neighbour) {
-                Some(value) if *value != ValueType::default() => true,
-                _ => false,
-            }
+            matches!(self.get(&neighbour), Some(value) if *value != ValueType::default())
         } else {
             false
         }
@@ -129,11 +126,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
     }
 
     fn getneighbour(&self, point: &Point<ScaleType>, direction: Direction) -> Option<Point<ScaleType>> {
-        if let Some(neighbour) = point.neighbour(direction, Some(self.width()), Some(self.height())) {
-            Some(neighbour)
-        } else {
-            None
-        }
+        point.neighbour(direction, Some(self.width()), Some(self.height()))
     }
 
     fn getneighbours(&self, point: &Point<ScaleType>) -> Vec<Point<ScaleType>> {
@@ -146,6 +139,71 @@ pub trait GenericGrid<ScaleType, ValueType> where
     }
 
 
+    ///Find the point nearest to the given point (other than the point itself) for which the
+    ///predicate holds. The search expands in square rings around the point, so only the
+    ///neighbourhood up to the nearest match is visited rather than the entire grid.
+    fn nearest(&self, point: &Point<ScaleType>, predicate: impl Fn(&Point<ScaleType>, &ValueType) -> bool) -> Option<Point<ScaleType>> {
+        let width = self.width_as_usize() as isize;
+        let height = self.height_as_usize() as isize;
+        let (px, py) = (point.xs() as isize, point.ys() as isize);
+        let mut mindistance: Option<f64> = None;
+        let mut closest: Option<Point<ScaleType>> = None;
+        for radius in 1..=max(width,height) {
+            //every point on this ring is at least radius away, nothing closer can follow
+            if mindistance.is_some() && radius as f64 > mindistance.unwrap() {
+                break;
+            }
+            for dy in -radius..=radius {
+                let y = py + dy;
+                if y < 0 || y >= height {
+                    continue;
+                }
+                //on the top and bottom edge of the ring we visit all cells, otherwise only the sides
+                let step = if dy.abs() == radius { 1 } else { 2 * radius };
+                let mut dx = -radius;
+                while dx <= radius {
+                    let x = px + dx;
+                    if x >= 0 && x < width {
+                        let candidate: Point<ScaleType> = Point::new_usize(x as usize, y as usize);
+                        if let Some(value) = self.get(&candidate) {
+                            if predicate(&candidate, value) {
+                                let distance = point.distance(&candidate);
+                                if mindistance.is_none() || distance < mindistance.unwrap() {
+                                    mindistance = Some(distance);
+                                    closest = Some(candidate);
+                                }
+                            }
+                        }
+                    }
+                    dx += step;
+                }
+            }
+        }
+        closest
+    }
+
+    ///Returns a new grid of the specified size with this grid (the generated sector) at the
+    ///top-left, reflected or rotated onto the rest according to the symmetry. Points that are
+    ///equivalent under the symmetry all take the value of the first one that is set, so seams where
+    ///the sector overlaps with its own copies remain symmetric as well.
+    fn symmetric(&self, width: ScaleType, height: ScaleType, symmetry: Symmetry) -> Grid<ScaleType,ValueType> {
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width, height);
+        for (point, value) in self.iter() {
+            grid.set(&point, value.clone());
+        }
+        for point in grid.rectangle().iter() {
+            let mut points = symmetry.points(&point, width, height);
+            points.sort();
+            let value: Option<ValueType> = points.iter().map(|point| grid[point].clone()).find(|value| *value != ValueType::default());
+            if let Some(value) = value {
+                for point in points.iter() {
+                    grid.set(point, value.clone());
+                }
+            }
+        }
+        grid
+    }
+
     fn get(&self, point: &Point<ScaleType>) -> Option<&ValueType> {
         self.get_data_vec().get(self.index(point))
     }
@@ -166,7 +224,7 @@ pub trait GenericGrid<ScaleType, ValueType> where
     //methods that need to be implemented:
     fn width(&self) -> ScaleType;
     fn height(&self) -> ScaleType;
-    fn iter(&self) -> GridIterator<ScaleType, ValueType>;
+    fn iter(&self) -> GridIterator<'_, ScaleType, ValueType>;
     fn get_data_vec(&self) -> &Vec<ValueType>;
     fn get_mut_data_vec(&mut self) -> &mut Vec<ValueType>;
 
@@ -185,8 +243,8 @@ impl<ScaleType,ValueType> GenericGrid<ScaleType,ValueType> for Grid<ScaleType,Va
         self.size.1
     }
 
-    fn iter(&self) -> GridIterator<ScaleType, ValueType> {
-        GridIterator { grid: &self, current: self.rectangle().iter() }
+    fn iter(&self) -> GridIterator<'_, ScaleType, ValueType> {
+        GridIterator { grid: self, current: self.rectangle().iter() }
     }
 
 
@@ -237,7 +295,7 @@ pub trait NumericGrid<'a,ScaleType, ValueType>: GenericGrid<ScaleType, ValueType
     }
 
     fn inc(&mut self, point: &Point<ScaleType>, amount: ValueType) -> bool {
-        let mut value = self.get_mut(point).expect("Point not found in grid!");
+        let value = self.get_mut(point).expect("Point not found in grid!");
         if let Some(result) = value.checked_add(&amount) {
             *value = result;
             true
@@ -249,7 +307,7 @@ pub trait NumericGrid<'a,ScaleType, ValueType>: GenericGrid<ScaleType, ValueType
     }
 
     fn dec(&mut self, point: &Point<ScaleType>, amount: ValueType) -> bool {
-        let mut value = self.get_mut(point).expect("Point not found in grid!");
+        let value = self.get_mut(point).expect("Point not found in grid!");
         if let Some(result) = value.checked_sub(&amount) {
             *value = result;
             true
@@ -260,7 +318,7 @@ pub trait NumericGrid<'a,ScaleType, ValueType>: GenericGrid<ScaleType, ValueType
         }
     }
 
-    fn randompathto(&mut self, rng: &mut Pcg32, from: &Point<ScaleType>, to: &Point<ScaleType>, value: ValueType) {
+    fn randompathto<R: Rng + ?Sized>(&mut self, rng: &mut R, from: &Point<ScaleType>, to: &Point<ScaleType>, value: ValueType) {
         let mut retry = true;
         let mut retries = 0;
         let mut walk = *from; //copy
@@ -291,12 +349,10 @@ pub trait NumericGrid<'a,ScaleType, ValueType>: GenericGrid<ScaleType, ValueType
     }
 
     ///Creates a rectangular path (only horizontal and vertical) between points A and B
-    fn rectpathto(&mut self, rng: &mut Pcg32, from: &Point<ScaleType>, to: &Point<ScaleType>, value: ValueType) {
+    fn rectpathto<R: Rng + ?Sized>(&mut self, rng: &mut R, from: &Point<ScaleType>, to: &Point<ScaleType>, value: ValueType) {
         if from == to {
             return;
         }
-        let dx = if to.x() > from.x() { Direction::East } else { Direction::West };
-        let dy = if to.y() > from.y() { Direction::South } else { Direction::North };
         let horizontal_first: bool = rng.gen();
         let xrange = range(min(from.x(),to.x()), max(from.x(),to.x()) + ScaleType::one());
         let yrange = range(min(from.y(),to.y()), max(from.y(),to.y()) + ScaleType::one());
@@ -367,7 +423,7 @@ impl<ScaleType,ValueType> fmt::Display for Grid<ScaleType,ValueType> where
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (i, (point, value)) in self.iter().enumerate() {
             if point.x() == ScaleType::zero() && i > 0 {
-                write!(f, "\n")?;
+                writeln!(f)?;
             }
             write!(f, "{}", value)?;
         }
@@ -429,6 +485,7 @@ impl<ScaleType,ValueType> fmt::Display for Grid<ScaleType,ValueType> where
 
 
 
+#[allow(dead_code)]
 #[derive(Eq,PartialEq)]
 struct PathState<ScaleType> {
    point: Point<ScaleType>,
@@ -436,7 +493,7 @@ struct PathState<ScaleType> {
 }
 
 
-#[derive(Eq,PartialEq,Default,Clone)]
+#[derive(Debug,Eq,PartialEq,Default,Clone)]
 pub struct RenderedTextCell {
     ///The background colour (R,G,B)
     pub background_colour: Option<(u8,u8,u8)>,
@@ -446,28 +503,10 @@ pub struct RenderedTextCell {
     pub text: Option<String>,
 }
 
-//TODO later: implement Add/AssignAdd to combine RenderedTextCells so we can combine different
-//layers
-
+///Writes the cell with true colours, see `paint()` for other colour modes
 impl fmt::Display for RenderedTextCell {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let text: &str = if let Some(s) = self.text.as_ref() {
-            s
-        } else {
-            " "
-        };
-        if self.background_colour.is_some() || self.foreground_colour.is_some() {
-            let mut style = ansi_term::Style::new();
-            if let Some((r,g,b)) = self.background_colour {
-                style = style.on(ansi_term::Colour::RGB(r,g,b));
-            }
-            if let Some((r,g,b)) = self.foreground_colour {
-                style = style.fg(ansi_term::Colour::RGB(r,g,b));
-            }
-            write!(f, "{}",style.paint(text))
-        } else {
-            write!(f, "{}",text)
-        }
+        write!(f, "{}", self.paint(ColourMode::TrueColour))
     }
 }
 
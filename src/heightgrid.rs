@@ -1,17 +1,79 @@
 use rand::{SeedableRng,Rng};
 use rand_pcg::Pcg32;
-use std::cmp::{min,PartialEq,Eq};
-use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,range,CheckedAdd,CheckedSub};
+use std::cmp::PartialEq;
+use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,CheckedAdd,CheckedSub};
 
-use crate::common::{Distance,Direction,Volume};
+use crate::common::Volume;
 use crate::point::Point;
 use crate::rectangle::Rectangle;
 use crate::grid::{Grid,GenericGrid,NumericGrid,RenderedTextCell};
+use crate::symmetry::Symmetry;
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter,checkdimensions,describesize};
+use crate::events::{GenerationEvent,Observer};
+use crate::svg::Svg;
+use crate::tiled::TiledMap;
 
-#[derive(Debug,Default)]
+#[derive(Debug,Clone,Default)]
 pub struct HeightGridProperties {
     ///number of iterations
     pub iterations: usize,
+
+    ///symmetry to enforce on the map
+    pub symmetry: Symmetry,
+}
+
+impl HeightGridProperties {
+    ///Starts building properties for a map of the given size, with the defaults of the `height`
+    ///generator
+    pub fn builder(width: usize, height: usize) -> HeightGridPropertiesBuilder {
+        HeightGridPropertiesBuilder {
+            width,
+            height,
+            properties: HeightGridProperties {
+                iterations: 90,
+                symmetry: Symmetry::None,
+            },
+        }
+    }
+
+    ///Checks that the properties can be used for a map of the given size: the raised rectangles
+    ///are at least 2x2
+    pub fn validate(&self, width: usize, height: usize) -> Result<(), Error> {
+        checkdimensions(width, height)?;
+        let (sectorwidth, sectorheight) = self.symmetry.sector(width, height);
+        if self.iterations > 0 && (sectorwidth < 2 || sectorheight < 2) {
+            return Err(Error::InvalidParameter(format!("A {} is too small to raise rectangles of at least 2x2 in", describesize(width, height, self.symmetry))));
+        }
+        Ok(())
+    }
+}
+
+///Builds `HeightGridProperties` that are checked against the size of the map, see
+///`HeightGridProperties::builder()`
+#[derive(Debug,Clone)]
+pub struct HeightGridPropertiesBuilder {
+    width: usize,
+    height: usize,
+    properties: HeightGridProperties,
+}
+
+impl HeightGridPropertiesBuilder {
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.properties.iterations = iterations;
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.properties.symmetry = symmetry;
+        self
+    }
+
+    ///Returns the properties, or a description of why they do not fit the map
+    pub fn build(self) -> Result<HeightGridProperties, Error> {
+        self.properties.validate(self.width, self.height)?;
+        Ok(self.properties)
+    }
 }
 
 #[derive(Debug,Clone,Copy)]
@@ -26,8 +88,12 @@ pub trait HeightGrid<ScaleType, ValueType> where
     ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
 
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties) -> Grid<ScaleType,ValueType>;
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
     fn render(&self, renderstyle: HeightRenderStyle) -> Grid<ScaleType,RenderedTextCell>;
     fn rendercell(&self, point: &Point<ScaleType>, min: ValueType, max: ValueType,renderstyle: HeightRenderStyle) -> RenderedTextCell;
+    fn svg(&self, cellsize: f64, renderstyle: HeightRenderStyle) -> Svg;
+    fn tiled(&self, tilesize: usize, renderstyle: HeightRenderStyle) -> TiledMap;
 }
 
 impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
@@ -35,15 +101,32 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
     ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
 
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties) -> Grid<ScaleType,ValueType> {
-        let mut rng = Pcg32::seed_from_u64(seed);
-        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width,height);
-        for i in 0..properties.iterations {
-            let rect: Rectangle<ScaleType> = Rectangle::random(&mut rng, &grid.rectangle(),
+        Self::generate_with_events(width, height, seed, properties, &mut |_, _| {})
+    }
+
+    ///Generates the height map like `generate()`, reporting every rectangle that is raised to the
+    ///observer
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties, observer)
+    }
+
+    ///Generates the height map like `generate_with_events()`, with a random number generator of the
+    ///given type seeded from `seed`
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: HeightGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        let mut rng = R::seed_from_u64(seed);
+        //with symmetry we only generate a single sector
+        let (sectorwidth, sectorheight) = properties.symmetry.sector(width, height);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(sectorwidth,sectorheight);
+        for _ in 0..properties.iterations {
+            let rect: Rectangle<ScaleType> = match Rectangle::random(&mut rng, &grid.rectangle(),
                                Some(ScaleType::one()),  //minwidth
-                               Some(ScaleType::from_usize(grid.width_as_usize() / 5).expect("conversion error")), //maxwidth
+                               Some(width / ScaleType::from_u8(5).unwrap()), //maxwidth
                                Some(ScaleType::one()),  //minheight
-                               Some(ScaleType::from_usize(grid.height_as_usize() / 5).expect("conversion error")), //maxheight
-            );
+                               Some(height / ScaleType::from_u8(5).unwrap()), //maxheight
+            ) {
+                Ok(rect) => rect,
+                Err(_) => break, //the map is too small for any rectangle
+            };
             for point in rect.iter() {
                 let cornercase: bool =  (rect.width() >= ScaleType::from_u8(3).unwrap()  && (point.x() == rect.topleft.x() || point.x() == rect.topright().x()))
                    && (point.y() >= ScaleType::from_u8(3).unwrap() && (point.y() == rect.topleft.y() || point.y() == rect.bottomright.y()));
@@ -51,6 +134,11 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
                     grid.inc(&point, ValueType::one());
                 }
             }
+            observer(&GenerationEvent::HeightRectangle(rect), &grid);
+        }
+        if properties.symmetry != Symmetry::None {
+            grid = grid.symmetric(width, height, properties.symmetry);
+            observer(&GenerationEvent::Symmetry(properties.symmetry), &grid);
         }
         grid
     }
@@ -60,7 +148,7 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
         let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
         let min = self.min();
         let max = self.max();
-        for (i, point) in self.rectangle().iter().enumerate() {
+        for point in self.rectangle().iter() {
             renderedgrid.set(&point,  HeightGrid::rendercell(self, &point, min, max, renderstyle) );
         }
         renderedgrid
@@ -70,16 +158,18 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
         let v = self[point].to_usize().unwrap();
         let min  = min.to_usize().unwrap();
         let max  = max.to_usize().unwrap();
+        //a flat map has no range, all of it is rendered as the lowest level
+        let range = (max - min).max(1);
         let (r,g,b): (u8,u8,u8) = match renderstyle {
             HeightRenderStyle::Simple => {
-                let colour: usize = (v - min) * (255/(max-min));
+                let colour: usize = (v - min) * (255/range);
                 let colour: u8 = colour as u8;
                 (colour,colour, colour)
             },
             HeightRenderStyle::HeatMap => {
                 //convert HSV (hue, saturation, value) to RGB, assuming saturation and value are
                 //always max (1)
-                let hue: f64 = 360.0 - ((v as f64 - min as f64) * (360.0/(max as f64 - min as f64)));
+                let hue: f64 = 360.0 - ((v as f64 - min as f64) * (360.0/range as f64));
                 let x: f64 = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
                 let x: u8 = (x * 255.0) as u8;
                 match hue {
@@ -92,7 +182,7 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
                 }
             },
             HeightRenderStyle::Terrain => {
-                let level: usize = (v - min) * (100/(max-min));
+                let level: usize = (v - min) * (100/range);
                 match level {
                     _ if level <= 15 => {
                         //deep water - blue
@@ -127,4 +217,115 @@ impl<ScaleType,ValueType> HeightGrid<ScaleType,ValueType> for Grid<ScaleType,Val
             text: None, //defaults to space
         }
     }
+
+    ///Renders the height map as vector graphics, one coloured square per cell
+    fn svg(&self, cellsize: f64, renderstyle: HeightRenderStyle) -> Svg {
+        let mut svg = Svg::new(self.width_as_usize() as f64 * cellsize, self.height_as_usize() as f64 * cellsize);
+        let min = self.min();
+        let max = self.max();
+        for point in self.rectangle().iter() {
+            if let Some(colour) = HeightGrid::rendercell(self, &point, min, max, renderstyle).background_colour {
+                svg.rect(point.xs() as f64 * cellsize, point.ys() as f64 * cellsize, cellsize, cellsize, colour);
+            }
+        }
+        svg
+    }
+
+    ///Exports the height map as a Tiled map, with a tile for every height between the minimum
+    ///and the maximum
+    fn tiled(&self, tilesize: usize, renderstyle: HeightRenderStyle) -> TiledMap {
+        let mut map = TiledMap::new(self.width_as_usize(), self.height_as_usize(), tilesize);
+        let min = self.min();
+        let max = self.max();
+        //render a representative cell for every height so the tiles get the same colours as the map
+        let mut samples: Vec<Option<Point<ScaleType>>> = vec![None; max.to_usize().unwrap() - min.to_usize().unwrap() + 1];
+        for (point, v) in self.iter() {
+            samples[v.to_usize().unwrap() - min.to_usize().unwrap()].get_or_insert(point);
+        }
+        let mut gids = Vec::with_capacity(samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            let appearance = match sample {
+                Some(point) => HeightGrid::rendercell(self, point, min, max, renderstyle),
+                None => RenderedTextCell { background_colour: None, foreground_colour: None, text: None },
+            };
+            gids.push(map.addtile(&format!("height {}", min.to_usize().unwrap() + i), appearance));
+        }
+        for (point, v) in self.iter() {
+            map.set(&point, gids[v.to_usize().unwrap() - min.to_usize().unwrap()]);
+        }
+        map
+    }
+}
+
+fn renderstyle(style: &str) -> HeightRenderStyle {
+    match style {
+        "heatmap" => HeightRenderStyle::HeatMap,
+        "terrain" => HeightRenderStyle::Terrain,
+        _ => HeightRenderStyle::Simple
+    }
+}
+
+///Generates height maps through `HeightGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct HeightGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for HeightGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = HeightGridProperties;
+
+    fn name(&self) -> &'static str {
+        "height"
+    }
+
+    fn description(&self) -> &'static str {
+        "A height map built up from many raised rectangles, good for landscapes"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("iterations", "Number of rectangles to raise", Some("90")),
+            ParameterInfo::new("symmetry", "Symmetry to enforce: none, horizontal, vertical, rotate2, rotate4", Some("none")),
+        )
+    }
+
+    fn styles(&self) -> &'static [&'static str] {
+        &["simple", "heatmap", "terrain"]
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<HeightGridProperties, Error> {
+        Ok(HeightGridProperties {
+            iterations: parameter(parameters, "iterations")?,
+            symmetry: parameter(parameters, "symmetry")?,
+        })
+    }
+
+    fn validate(&self, width: ScaleType, height: ScaleType, properties: &HeightGridProperties) -> Result<(), Error> {
+        properties.validate(width.to_usize().ok_or(Error::OutOfBounds)?, height.to_usize().ok_or(Error::OutOfBounds)?)
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &HeightGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as HeightGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone()))
+    }
+
+    fn generate_with_events(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &HeightGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as HeightGrid<ScaleType,ValueType>>::generate_with_events(width, height, seed, properties.clone(), observer))
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, style: &str, _properties: &HeightGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        HeightGrid::render(grid, renderstyle(style))
+    }
+
+    fn svg(&self, grid: &Grid<ScaleType,ValueType>, cellsize: f64, style: &str) -> Option<Svg> {
+        Some(HeightGrid::svg(grid, cellsize, renderstyle(style)))
+    }
+
+    fn tiled(&self, grid: &Grid<ScaleType,ValueType>, tilesize: usize, style: &str) -> Option<TiledMap> {
+        Some(HeightGrid::tiled(grid, tilesize, renderstyle(style)))
+    }
+
+    fn describe(&self, grid: &Grid<ScaleType,ValueType>, point: &Point<ScaleType>) -> String {
+        format!("height {}", grid[point].to_usize().unwrap())
+    }
 }
@@ -0,0 +1,65 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::fmt::Write;
+
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::image::{DEFAULT_BACKGROUND,DEFAULT_FOREGROUND};
+use crate::svg::hexcolour;
+
+///Escapes text for use in HTML content and attribute values
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub trait HtmlGrid<ScaleType> {
+    fn html(&self, title: &str, info: impl Fn(&Point<ScaleType>) -> String) -> String;
+}
+
+impl<ScaleType> HtmlGrid<ScaleType> for Grid<ScaleType,RenderedTextCell> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Renders the grid as a single self-contained HTML page with a coloured monospace grid. Hovering
+    ///over a cell shows its coordinates followed by whatever the info function returns for it
+    ///(e.g. the raw value of the underlying map).
+    fn html(&self, title: &str, info: impl Fn(&Point<ScaleType>) -> String) -> String {
+        let mut html = String::new();
+        writeln!(html, "<!DOCTYPE html>").unwrap();
+        writeln!(html, "<html>\n<head>\n<meta charset=\"utf-8\"/>\n<title>{}</title>", escape(title)).unwrap();
+        writeln!(html, "<style>").unwrap();
+        writeln!(html, "body {{ background: {}; color: {}; }}", hexcolour(DEFAULT_BACKGROUND), hexcolour(DEFAULT_FOREGROUND)).unwrap();
+        writeln!(html, "pre {{ font-family: monospace; line-height: 1; }}").unwrap();
+        writeln!(html, "pre span:hover {{ outline: 1px solid #ff0; }}").unwrap();
+        writeln!(html, "</style>\n</head>\n<body>\n<pre>").unwrap();
+        for (point, cell) in self.iter() {
+            let mut style = String::new();
+            if let Some(colour) = cell.foreground_colour {
+                write!(style, "color:{};", hexcolour(colour)).unwrap();
+            }
+            if let Some(colour) = cell.background_colour {
+                write!(style, "background:{};", hexcolour(colour)).unwrap();
+            }
+            let text = cell.text.as_deref().unwrap_or(" ");
+            let tooltip = format!("{},{}: {}", point.xs(), point.ys(), info(&point));
+            if style.is_empty() {
+                write!(html, "<span title=\"{}\">{}</span>", escape(&tooltip), escape(text)).unwrap();
+            } else {
+                write!(html, "<span style=\"{}\" title=\"{}\">{}</span>", style, escape(&tooltip), escape(text)).unwrap();
+            }
+            if point.xs() + 1 == self.width_as_usize() {
+                html.push('\n');
+            }
+        }
+        writeln!(html, "</pre>\n</body>\n</html>").unwrap();
+        html
+    }
+}
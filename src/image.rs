@@ -0,0 +1,128 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::fs::File;
+use std::io::{self,Write,BufWriter};
+use std::path::Path;
+use png;
+
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::pipegrid::pipearms;
+use crate::colour::blend;
+
+///Colour used for cells without a background colour (as on a dark terminal)
+pub const DEFAULT_BACKGROUND: (u8,u8,u8) = (0,0,0);
+///Colour used for glyphs without a foreground colour
+pub const DEFAULT_FOREGROUND: (u8,u8,u8) = (204,204,204);
+
+///A simple RGB raster image
+#[derive(Debug,Clone,PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    ///flattened, row by row
+    pub pixels: Vec<(u8,u8,u8)>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, colour: (u8,u8,u8)) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![colour; width * height],
+        }
+    }
+
+    ///Fills a rectangle with the given colour, anything outside the image is clipped
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, colour: (u8,u8,u8)) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                self.pixels[py * self.width + px] = colour;
+            }
+        }
+    }
+
+    ///Writes the image as a binary PPM (P6)
+    pub fn write_ppm(&self, writer: &mut impl Write) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let data: Vec<u8> = self.pixels.iter().flat_map(|(r,g,b)| vec!(*r,*g,*b)).collect();
+        writer.write_all(&data)
+    }
+
+    ///Writes the image as a PNG
+    pub fn write_png(&self, writer: impl Write) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        let data: Vec<u8> = self.pixels.iter().flat_map(|(r,g,b)| vec!(*r,*g,*b)).collect();
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    ///Saves the image to file, the format (PNG or PPM) is determined by the extension
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+            Some("ppm") => self.write_ppm(&mut BufWriter::new(File::create(filename)?)),
+            Some("png") => self.write_png(BufWriter::new(File::create(filename)?)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Unsupported image format, use .png or .ppm")),
+        }
+    }
+}
+
+///Draws a single glyph as simple shapes into the cell at the given pixel offset
+fn drawglyph(image: &mut Image, glyph: char, x: usize, y: usize, cellsize: usize, foreground: (u8,u8,u8), background: (u8,u8,u8)) {
+    match glyph {
+        ' ' => {},
+        '█' => image.fill(x, y, cellsize, cellsize, foreground),
+        '▀' => image.fill(x, y, cellsize, cellsize / 2, foreground),
+        '▄' => image.fill(x, y + cellsize / 2, cellsize, cellsize - cellsize / 2, foreground),
+        '░' => image.fill(x, y, cellsize, cellsize, blend(background, foreground, 0.25)),
+        '▒' => image.fill(x, y, cellsize, cellsize, blend(background, foreground, 0.5)),
+        '▓' => image.fill(x, y, cellsize, cellsize, blend(background, foreground, 0.75)),
+        _ => {
+            if let Some(([north, east, south, west], heavy)) = pipearms(glyph) {
+                let thickness = if heavy { (cellsize / 3).max(2) } else { (cellsize / 6).max(1) }.min(cellsize);
+                let start = (cellsize - thickness) / 2;
+                let end = start + thickness;
+                image.fill(x + start, y + start, thickness, thickness, foreground);
+                if north { image.fill(x + start, y, thickness, end, foreground); }
+                if east { image.fill(x + start, y + start, cellsize - start, thickness, foreground); }
+                if south { image.fill(x + start, y + start, thickness, cellsize - start, foreground); }
+                if west { image.fill(x, y + start, end, thickness, foreground); }
+            } else {
+                //any other glyph is drawn as a square dot
+                let size = match glyph {
+                    '.' | '·' | ',' => (cellsize / 5).max(1),
+                    _ => (cellsize / 2).max(1),
+                };
+                let offset = (cellsize - size) / 2;
+                image.fill(x + offset, y + offset, size, size, foreground);
+            }
+        }
+    }
+}
+
+pub trait RasterGrid<ScaleType> {
+    fn rasterize(&self, cellsize: usize) -> Image;
+}
+
+impl<ScaleType> RasterGrid<ScaleType> for Grid<ScaleType,RenderedTextCell> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Draws the rendered grid as an image with square cells of the given number of pixels
+    fn rasterize(&self, cellsize: usize) -> Image {
+        let mut image = Image::new(self.width_as_usize() * cellsize, self.height_as_usize() * cellsize, DEFAULT_BACKGROUND);
+        for (point, cell) in self.iter() {
+            let x = point.xs() * cellsize;
+            let y = point.ys() * cellsize;
+            let background = cell.background_colour.unwrap_or(DEFAULT_BACKGROUND);
+            let foreground = cell.foreground_colour.unwrap_or(DEFAULT_FOREGROUND);
+            image.fill(x, y, cellsize, cellsize, background);
+            if let Some(text) = cell.text.as_ref() {
+                if let Some(glyph) = text.chars().next() {
+                    drawglyph(&mut image, glyph, x, y, cellsize, foreground, background);
+                }
+            }
+        }
+        image
+    }
+}
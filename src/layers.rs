@@ -0,0 +1,169 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::fmt;
+use std::ops::{Add,AddAssign};
+use std::str::FromStr;
+
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::image::DEFAULT_BACKGROUND;
+use crate::colour::blend;
+
+///How a layer is combined with the layers below it
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum BlendRule {
+    ///everything the layer sets (text, foreground and background colour) replaces what is below,
+    ///what it leaves unset shows through
+    Override,
+    ///only the background colour is used, mixed over the background below with the given opacity
+    ///(0.0-1.0), the text below is kept
+    BackgroundAlpha(f64),
+    ///only the text and its foreground colour are drawn, over the background below
+    TextOverlay,
+}
+
+///Returns the text of the cell unless it is blank, blank text does not cover anything
+fn visibletext(cell: &RenderedTextCell) -> Option<&String> {
+    cell.text.as_ref().filter(|text| !text.trim().is_empty())
+}
+
+impl BlendRule {
+    ///Combines a cell with the cell below it
+    pub fn apply(&self, below: &RenderedTextCell, above: &RenderedTextCell) -> RenderedTextCell {
+        let mut cell = below.clone();
+        match self {
+            BlendRule::Override => {
+                if above.background_colour.is_some() {
+                    cell.background_colour = above.background_colour;
+                }
+                if let Some(text) = visibletext(above) {
+                    cell.text = Some(text.clone());
+                    cell.foreground_colour = above.foreground_colour;
+                }
+            },
+            BlendRule::BackgroundAlpha(alpha) => {
+                if let Some(colour) = above.background_colour {
+                    cell.background_colour = Some(blend(below.background_colour.unwrap_or(DEFAULT_BACKGROUND), colour, alpha.clamp(0.0, 1.0)));
+                }
+            },
+            BlendRule::TextOverlay => {
+                if let Some(text) = visibletext(above) {
+                    cell.text = Some(text.clone());
+                    cell.foreground_colour = above.foreground_colour;
+                }
+            },
+        }
+        cell
+    }
+}
+
+impl FromStr for BlendRule {
+    type Err = String;
+
+    ///Parses `override`, `text`, or `alpha` with an optional opacity (`alpha:0.3`, defaults to 0.5)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "override" => Ok(BlendRule::Override),
+            "text" => Ok(BlendRule::TextOverlay),
+            "alpha" => Ok(BlendRule::BackgroundAlpha(0.5)),
+            _ => match s.strip_prefix("alpha:").map(|alpha| alpha.parse::<f64>()) {
+                Some(Ok(alpha)) if (0.0..=1.0).contains(&alpha) => Ok(BlendRule::BackgroundAlpha(alpha)),
+                _ => Err(format!("No such blend rule: {}", s)),
+            }
+        }
+    }
+}
+
+impl fmt::Display for BlendRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlendRule::Override => write!(f, "override"),
+            BlendRule::BackgroundAlpha(alpha) => write!(f, "alpha:{}", alpha),
+            BlendRule::TextOverlay => write!(f, "text"),
+        }
+    }
+}
+
+///Draws the right-hand cell over the left-hand cell, as `BlendRule::Override`
+impl Add for RenderedTextCell {
+    type Output = RenderedTextCell;
+
+    fn add(self, other: RenderedTextCell) -> RenderedTextCell {
+        BlendRule::Override.apply(&self, &other)
+    }
+}
+
+impl AddAssign for RenderedTextCell {
+    fn add_assign(&mut self, other: RenderedTextCell) {
+        *self = BlendRule::Override.apply(self, &other);
+    }
+}
+
+///A rendered grid and how it is combined with the layers below it
+#[derive(Clone)]
+pub struct Layer<ScaleType> {
+    pub grid: Grid<ScaleType,RenderedTextCell>,
+    pub rule: BlendRule,
+}
+
+///A stack of rendered grids, from bottom to top, that can be flattened into a single grid
+#[derive(Clone)]
+pub struct LayerStack<ScaleType> {
+    pub base: Grid<ScaleType,RenderedTextCell>,
+    pub layers: Vec<Layer<ScaleType>>,
+}
+
+impl<ScaleType> LayerStack<ScaleType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy {
+
+    ///Starts a stack with the bottom layer, which also determines the size of the result
+    pub fn new(base: Grid<ScaleType,RenderedTextCell>) -> LayerStack<ScaleType> {
+        LayerStack {
+            base,
+            layers: Vec::new(),
+        }
+    }
+
+    ///Adds a layer on top of the stack
+    pub fn push(&mut self, grid: Grid<ScaleType,RenderedTextCell>, rule: BlendRule) {
+        self.layers.push(Layer { grid, rule });
+    }
+
+    ///Combines all layers from bottom to top into a single grid. Parts of layers outside the
+    ///bottom layer are cut off.
+    pub fn flatten(&self) -> Grid<ScaleType,RenderedTextCell> {
+        let mut result = self.base.clone();
+        let (width, height) = (result.width_as_usize(), result.height_as_usize());
+        for layer in self.layers.iter() {
+            for (point, cell) in layer.grid.iter() {
+                //get() does not check the bounds of each dimension, a point beyond the right edge would wrap
+                if point.xs() >= width || point.ys() >= height {
+                    continue;
+                }
+                if let Some(below) = result.get(&point) {
+                    let combined = layer.rule.apply(below, cell);
+                    result.set(&point, combined);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    #[test]
+    fn larger_layers_are_cut_off() {
+        let mut stack: LayerStack<u16> = LayerStack::new(Grid::new(3, 2));
+        let mut overlay: Grid<u16,RenderedTextCell> = Grid::new(5, 1);
+        for x in 0..5 {
+            overlay.set(&Point(x, 0), RenderedTextCell { text: Some(x.to_string()), ..RenderedTextCell::default() });
+        }
+        stack.push(overlay, BlendRule::Override);
+        let result = stack.flatten();
+        let text = |x: u16, y: u16| result[&Point(x, y)].text.clone().unwrap_or_default();
+        assert_eq!((0..3).map(|x| text(x, 0)).collect::<String>(), "012");
+        assert_eq!((0..3).map(|x| text(x, 1)).collect::<String>(), "");
+    }
+}
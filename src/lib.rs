@@ -0,0 +1,74 @@
+//! Procedural map generation: pipe networks, height maps, rooms, mazes, caves and wave function
+//! collapse maps on generic grids, with renderers for the terminal, images, SVG, HTML and the
+//! Tiled map editor.
+//!
+//! Every map type is a trait implemented on [`Grid`](grid::Grid), generated from a seed and a
+//! properties struct:
+//!
+//! ```
+//! use procmapgen::prelude::*;
+//!
+//! let grid: Grid<u16,u8> = <Grid<u16,u8> as RoomGrid<u16,u8>>::generate(60, 30, 42, RoomGridProperties {
+//!     rooms: 6,
+//!     symmetry: Symmetry::None,
+//! });
+//! print!("{}", RoomGrid::render(&grid).paint(ColourMode::None));
+//! ```
+//!
+//! The [`prelude`] re-exports what is needed to generate and render maps, the modules hold the
+//! rest.
+
+extern crate rand;
+extern crate num;
+extern crate ansi_term;
+
+pub mod grid;
+pub mod point;
+pub mod rectangle;
+pub mod common;
+pub mod pipegrid;
+pub mod heightgrid;
+pub mod roomgrid;
+pub mod mazegrid;
+pub mod wfc;
+pub mod cavegrid;
+pub mod image;
+pub mod symmetry;
+pub mod svg;
+pub mod html;
+pub mod serialization;
+pub mod tiled;
+pub mod textmap;
+pub mod blockrender;
+pub mod colour;
+pub mod layers;
+pub mod events;
+pub mod generator;
+pub mod error;
+pub mod config;
+pub mod manifest;
+pub mod seed;
+pub mod batch;
+
+///The grid types, geometry and generators, for use as `use procmapgen::prelude::*`
+pub mod prelude {
+    pub use crate::grid::{Grid,GenericGrid,NumericGrid,RenderedTextCell};
+    pub use crate::point::Point;
+    pub use crate::rectangle::Rectangle;
+    pub use crate::common::{Direction,Distance,Volume};
+    pub use crate::symmetry::Symmetry;
+    pub use crate::pipegrid::{PipeGrid,PipeGenerator,PipeGridProperties,PipeGridPropertiesBuilder,PipeRenderStyle,InterconnectTarget};
+    pub use crate::heightgrid::{HeightGrid,HeightGenerator,HeightGridProperties,HeightGridPropertiesBuilder,HeightRenderStyle};
+    pub use crate::roomgrid::{RoomGrid,RoomGenerator,RoomGridProperties,RoomGridPropertiesBuilder};
+    pub use crate::mazegrid::{MazeGrid,MazeGenerator,MazeGridProperties,MazeAlgorithm};
+    pub use crate::wfc::{WfcGrid,WfcGenerator,WfcGridProperties,TileSet};
+    pub use crate::cavegrid::{CaveGrid,CaveGenerator,CaveGridProperties};
+    pub use crate::events::GenerationEvent;
+    pub use crate::generator::{MapGenerator,Registry,Parameters};
+    pub use crate::colour::{ColourMode,PaintGrid};
+    pub use crate::image::RasterGrid;
+    pub use crate::error::Error;
+    pub use crate::config::Config;
+    pub use crate::manifest::Manifest;
+    pub use crate::seed::Seed;
+}
@@ -1,26 +1,344 @@
 extern crate rand;
 extern crate clap;
-extern crate num;
-extern crate ansi_term;
-
-pub mod grid;
-pub mod point;
-pub mod rectangle;
-pub mod common;
-pub mod pipegrid;
-pub mod heightgrid;
-pub mod roomgrid;
-
-use clap::{App,Arg};
+
+mod viewer;
+
+use clap::{App,Arg,ArgMatches,SubCommand};
 use std::iter::Iterator;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self,Write};
 use std::thread;
+use std::sync::atomic::{AtomicUsize,Ordering};
 use std::time;
 
-use grid::Grid;
-use pipegrid::{PipeGrid,PipeGridProperties,PipeRenderStyle};
-use heightgrid::{HeightGrid,HeightGridProperties,HeightRenderStyle};
-use roomgrid::{RoomGrid,RoomGridProperties};
+use procmapgen::prelude::*;
+use procmapgen::svg::Svg;
+use procmapgen::tiled::TiledMap;
+use procmapgen::textmap::{TextGrid,parsechartable};
+use procmapgen::blockrender::{HalfBlockGrid,BrailleGrid};
+use procmapgen::layers::{LayerStack,BlendRule};
+use procmapgen::html::HtmlGrid;
+use procmapgen::serialization::SerializableGrid;
+use procmapgen::pipegrid::renderstyle;
+use procmapgen::generator::{DynMapGenerator,withdefaults};
+use procmapgen::batch::Batch;
+use viewer::ViewerSettings;
+
+
+///The rendered map and the exports that need the underlying map (only built when requested),
+///followed by the map itself
+type Outputs = (Grid<u16,RenderedTextCell>, Option<Svg>, Option<TiledMap>, Grid<u16,u8>);
+
+///A map, with the connections between its cells if it was read from box-drawing characters
+type LoadedMap = (Grid<u16,u8>, Option<Grid<u16,u8>>);
+
+///Loads the map from file if one was given, otherwise generates it. Text maps (.txt) are read with
+///the character table if one was given, or as box-drawing pipes otherwise, in which case the
+///connections between the cells as drawn are returned as well.
+fn loadorgenerate(input: Option<&str>, chartable: Option<&str>, generate: impl FnOnce() -> Result<Grid<u16,u8>, Error>) -> Result<LoadedMap, Error> {
+    match input {
+        Some(filename) if filename.ends_with(".txt") => {
+            let text = fs::read_to_string(filename)?;
+            if let Some(chartable) = chartable {
+                let table = parsechartable(chartable)?;
+                Ok((Grid::from_text(&text, &table, None)?, None))
+            } else {
+                let (grid, connections) = Grid::from_pipetext(&text)?;
+                Ok((grid, Some(connections)))
+            }
+        },
+        Some(filename) => Ok((Grid::load(filename)?, None)),
+        None => Ok((generate()?, None)),
+    }
+}
+
+///Parameters changed in the viewer, these take precedence over the command line
+type Overrides = HashMap<&'static str,String>;
+
+///Options that can be set in a configuration file, besides the parameters of the generators
+const CONFIGOPTIONS: &[&str] = &["type", "style", "width", "height", "seed", "color", "blocks", "overlay", "blend", "cellsize", "output", "input", "chars", "animate", "loop", "manifest"];
+
+///The options of a run: as given on the command line, or else as set in the configuration file,
+///or else the defaults of the command line
+struct Options<'a> {
+    argmatches: ArgMatches<'a>,
+    config: Config,
+}
+
+impl<'a> Options<'a> {
+    fn value(&self, name: &str) -> Option<&str> {
+        if self.argmatches.occurrences_of(name) > 0 {
+            self.argmatches.value_of(name)
+        } else {
+            self.config.option(name).or_else(|| self.argmatches.value_of(name))
+        }
+    }
+
+    ///Whether an option or flag is given, flags are set with true or false in the configuration file
+    fn is_present(&self, name: &str) -> bool {
+        if self.argmatches.occurrences_of(name) > 0 {
+            true
+        } else if let Some(value) = self.config.option(name) {
+            value != "false"
+        } else {
+            self.argmatches.is_present(name)
+        }
+    }
+
+    ///The value of a parameter of the given generator, the configuration file may set it for all
+    ///generators or for this generator only
+    fn parameter(&self, generator: &str, name: &str) -> Option<&str> {
+        if self.argmatches.occurrences_of(name) > 0 {
+            //flags without a value
+            self.argmatches.value_of(name).or(Some("true"))
+        } else {
+            self.config.parameter(generator, name).or_else(|| self.argmatches.value_of(name))
+        }
+    }
+}
+
+///Looks up an option, from the overrides or else from the options of the run
+fn param<'a>(options: &'a Options, overrides: &'a Overrides, name: &str) -> Option<&'a str> {
+    overrides.get(name).map(|value| value.as_str()).or_else(|| options.value(name))
+}
+
+///The parameters of a generator, from the overrides or else from the options of the run. The
+///parameters of generators have the same names as the command line options.
+fn parameters(generator: &dyn DynMapGenerator, options: &Options, overrides: &Overrides) -> Parameters {
+    let mut parameters = Parameters::new();
+    for info in generator.parameters() {
+        if let Some(value) = overrides.get(info.name).map(|value| value.as_str()).or_else(|| options.parameter(generator.name(), info.name)) {
+            parameters.insert(info.name.to_string(), value.to_string());
+        }
+    }
+    parameters
+}
+
+///Fails if symmetry is requested for a generator that does not support it, rather than silently
+///generating an asymmetric map
+fn checksymmetry(generator: &dyn DynMapGenerator, options: &Options) -> Result<(), String> {
+    let symmetric = options.parameter(generator.name(), "symmetry").is_some_and(|symmetry| symmetry != "none");
+    if symmetric && !generator.parameters().iter().any(|info| info.name == "symmetry") {
+        Err(format!("Symmetry is not supported for {} maps", generator.name()))
+    } else {
+        Ok(())
+    }
+}
+
+///The configuration that reproduces a run: its options, with the seed that was used, and all
+///parameters (including the defaults) of the generators used
+fn effectiveconfig(registry: &Registry, options: &Options, seed: u64) -> Config {
+    let mut config = Config::new();
+    for name in CONFIGOPTIONS {
+        if let Some(value) = options.value(name) {
+            config.options.insert(name.to_string(), value.to_string());
+        } else if options.is_present(name) {
+            config.options.insert(name.to_string(), "true".to_string());
+        }
+    }
+    config.options.insert("seed".to_string(), seed.to_string());
+    for maptype in [options.value("type"), options.value("overlay")].iter().flatten() {
+        if let Some(generator) = registry.get(maptype) {
+            let parameters = withdefaults(&parameters(generator, options, &Overrides::new()), &generator.parameters());
+            config.generators.insert(generator.name().to_string(), parameters.into_iter().collect());
+        }
+    }
+    config
+}
+
+///Generates a map of the given type with the parameters from the command line (or their
+///overrides), or loads it if an input file is given, and renders it. Returns a message if there is
+///no such type or the map can not be generated.
+fn generate(registry: &Registry, maptype: &str, options: &Options, overrides: &Overrides, seed: u64, input: Option<&str>) -> Result<Outputs, String> {
+    let generator = registry.get(maptype).ok_or("No such type, see --list for the available types")?;
+    let cellsize = param(options, overrides, "cellsize").unwrap().parse::<usize>().expect("Invalid cell size");
+    let width =  param(options, overrides, "width").unwrap().parse::<u16>().expect("Invalid width");
+    let height = param(options, overrides, "height").unwrap().parse::<u16>().expect("Invalid height");
+    let style = param(options, overrides, "style").unwrap();
+    let chartable = param(options, overrides, "chars");
+    let svgoutput = param(options, overrides, "output").is_some_and(|filename| filename.ends_with(".svg"));
+    let tiledoutput = param(options, overrides, "output").is_some_and(|filename| filename.ends_with(".tmx") || filename.ends_with(".tmj"));
+    //the viewer does not animate
+    let animate = options.is_present("animate") && !options.is_present("view");
+    if input.is_none() {
+        checksymmetry(generator, options)?;
+    }
+    let parameters = parameters(generator, options, overrides);
+    let (grid, connections) = loadorgenerate(input, chartable, || generator.generate(width, height, seed, &parameters, &mut |event, grid| if animate {
+        showframe(options, event, generator.render(grid, style, &parameters).expect("Invalid parameters"))
+    })).map_err(|err| match input {
+        Some(filename) => format!("Unable to load {}: {}", filename, err),
+        None => err.to_string(),
+    })?;
+    if maptype == "pipes" && !options.is_present("view") && (connections.is_some() || parameters.get("interconnect").is_some_and(|interconnect| interconnect != "false") || parameters.get("prune").is_some_and(|prune| prune != "0")) {
+        let deadends = match connections.as_ref() {
+            Some(connections) => grid.deadends_with_connections(connections),
+            None => grid.deadends(),
+        };
+        eprintln!("Dead ends remaining: {}", deadends.len());
+    }
+    let renderedgrid = match connections.as_ref() {
+        //pipes read from text are drawn as they were connected in the text
+        Some(connections) if maptype == "pipes" || maptype == "maze" => grid.render_with_connections(connections, renderstyle(style)),
+        _ => generator.render(&grid, style, &parameters).map_err(|err| err.to_string())?,
+    };
+    Ok((renderedgrid,
+          if svgoutput { generator.svg(&grid, cellsize as f64, style) } else { None },
+          if tiledoutput { generator.tiled(&grid, cellsize, style) } else { None }, grid))
+}
+
+///Lists the available map types with their parameters
+fn list(registry: &Registry) {
+    for generator in registry.iter() {
+        println!("{}: {}", generator.name(), generator.description());
+        if !generator.styles().is_empty() {
+            println!("    styles: {}", generator.styles().join(", "));
+        }
+        for info in generator.parameters() {
+            match info.default {
+                Some(default) => println!("    --{} (default {}): {}", info.name, default, info.description),
+                None => println!("    --{}: {}", info.name, info.description),
+            }
+        }
+    }
+}
+
+///Shows an intermediate state of the map while it is generated, with a line describing the step
+fn showframe(options: &Options, event: &GenerationEvent<u16>, renderedgrid: Grid<u16,RenderedTextCell>) {
+    let delay = options.value("animate").unwrap().parse::<u64>().expect("Invalid animation delay");
+    //frames are drawn over each other from the top left, clearing the screen every frame flickers
+    print!("\x1b[H{}{}\x1b[K\n\x1b[J", renderedgrid.paint(colourmode(options)), event);
+    io::stdout().flush().expect("Unable to write to standard output");
+    thread::sleep(time::Duration::from_millis(delay));
+}
+
+///The colour mode requested on the command line, or detected from the environment
+fn colourmode(options: &Options) -> ColourMode {
+    match options.value("color").unwrap() {
+        "auto" => ColourMode::detect(),
+        mode => mode.parse::<ColourMode>().expect("Invalid colour mode"),
+    }
+}
+
+///Draws the overlay map, if one was requested, over the rendered map. Returns a message if there
+///is no such overlay type or the overlay can not be generated.
+fn addoverlay(registry: &Registry, renderedgrid: Grid<u16,RenderedTextCell>, options: &Options, overrides: &Overrides, seed: u64) -> Result<Grid<u16,RenderedTextCell>, String> {
+    if let Some(overlaytype) = options.value("overlay") {
+        if registry.get(overlaytype).is_none() {
+            return Err("No such overlay type, see --list for the available types".to_string());
+        }
+        //the overlay is always generated, with the same seed and dimensions
+        let (overlay, _, _, _) = generate(registry, overlaytype, options, overrides, seed, None)?;
+        let mut stack = LayerStack::new(renderedgrid);
+        stack.push(overlay, options.value("blend").unwrap().parse::<BlendRule>().expect("Invalid blend rule"));
+        Ok(stack.flatten())
+    } else {
+        Ok(renderedgrid)
+    }
+}
 
+///Generates the map of a manifest again and compares it with the hash in the manifest. Returns
+///whether the map is the same.
+fn verify(registry: &Registry, filename: &str) -> bool {
+    let manifest = match Manifest::load(filename) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            eprintln!("Unable to load {}: {}", filename, err);
+            return false;
+        }
+    };
+    match manifest.verify(registry) {
+        Ok(hash) if hash == manifest.hash => {
+            println!("OK: {} map {}x{} with seed {} has hash {}", manifest.generator, manifest.width, manifest.height, manifest.seed, hash);
+            true
+        },
+        Ok(hash) => {
+            println!("MISMATCH: {} map {}x{} with seed {} has hash {}, expected {}", manifest.generator, manifest.width, manifest.height, manifest.seed, hash, manifest.hash);
+            if manifest.version != env!("CARGO_PKG_VERSION") {
+                println!("The manifest was written by version {}, this is version {}", manifest.version, env!("CARGO_PKG_VERSION"));
+            }
+            false
+        },
+        Err(err) => {
+            eprintln!("Unable to generate the map of {}: {}", filename, err);
+            false
+        }
+    }
+}
+
+///Generates a batch of maps into a directory, over the seeds and parameter values given to the
+///batch command, with the other options of the run. Returns whether all maps were generated.
+fn batch(registry: &Registry, options: &Options, argmatches: &ArgMatches) -> bool {
+    let maptype = options.value("type").unwrap();
+    let generator = match registry.get(maptype) {
+        Some(generator) => generator,
+        None => {
+            eprintln!("No such type, see --list for the available types");
+            return false;
+        }
+    };
+    if let Err(err) = checksymmetry(generator, options) {
+        eprintln!("{}", err);
+        return false;
+    }
+    let seeds = match procmapgen::batch::seeds(argmatches.value_of("seeds").unwrap()) {
+        Ok(seeds) => seeds,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+    //repeated parameters and values separated by | are alternatives
+    let mut varied: Vec<(String, Vec<String>)> = Vec::new();
+    for assignment in argmatches.values_of("vary").into_iter().flatten() {
+        let (name, values) = match assignment.split_once('=') {
+            Some((name, values)) if generator.parameters().iter().any(|info| info.name == name) => (name, values),
+            _ => {
+                eprintln!("Invalid --vary {}, expected a parameter of {} and its values, e.g. name=value1|value2", assignment, maptype);
+                return false;
+            }
+        };
+        let values = values.split('|').map(|value| value.to_string());
+        match varied.iter_mut().find(|(other, _)| other == name) {
+            Some((_, alternatives)) => alternatives.extend(values),
+            None => varied.push((name.to_string(), values.collect())),
+        }
+    }
+    let threads = match argmatches.value_of("threads") {
+        Some(threads) => threads.parse::<usize>().expect("Invalid number of threads"),
+        None => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+    };
+    let mut batch = Batch::new(maptype,
+                               options.value("width").unwrap().parse::<u16>().expect("Invalid width"),
+                               options.value("height").unwrap().parse::<u16>().expect("Invalid height"),
+                               parameters(generator, options, &Overrides::new()), &seeds, &varied);
+    batch.style = options.value("style").unwrap().to_string();
+    batch.cellsize = options.value("cellsize").unwrap().parse::<usize>().expect("Invalid cell size");
+    let directory = argmatches.value_of("directory").unwrap();
+    let total = batch.jobs.len();
+    let done = AtomicUsize::new(0);
+    let results = batch.run(registry, directory, threads, &|(job, result)| {
+        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Err(err) = result {
+            eprintln!("\r{}: {}", batch.mapdirectory(job), err);
+        }
+        eprint!("\r{}/{} maps", done, total);
+    });
+    eprintln!();
+    match results {
+        Ok(results) => {
+            let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+            eprintln!("Generated {} maps in {}, see {}/summary.csv{}", total - failed, directory, directory,
+                      if failed > 0 { format!(" ({} failed)", failed) } else { String::new() });
+            failed == 0
+        },
+        Err(err) => {
+            eprintln!("Unable to generate batch in {}: {}", directory, err);
+            false
+        }
+    }
+}
 
 fn main() {
     let argmatches = App::new("mapgen")
@@ -42,7 +360,7 @@ fn main() {
              .default_value("30")
         )
         .arg(Arg::with_name("seed")
-             .help("seed (0 = random seed)")
+             .help("seed, a number or any text such as castle-42 (0 = random seed)")
              .long("seed")
              .short("s")
              .takes_value(true)
@@ -54,6 +372,15 @@ fn main() {
              .short("l")
              .takes_value(true)
         )
+        .arg(Arg::with_name("animate")
+             .help("Show every step of the generation with this many milliseconds in between, before showing the map. For pipe, height and room maps")
+             .long("animate")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("view")
+             .help("Interactive viewer: change the seed, type, style and parameters with the keyboard and pan over maps larger than the terminal. With --loop it starts picking new seeds automatically, press 'a' to pause")
+             .long("view")
+        )
         .arg(Arg::with_name("backboneseeds")
              .help("backboneseeds")
              .long("backboneseeds")
@@ -73,6 +400,23 @@ fn main() {
              .long("interconnect")
              .short("x")
         )
+        .arg(Arg::with_name("interconnecttarget")
+             .help("(For pipe maps) What to interconnect dead ends with: deadends (default), pipes")
+             .long("interconnecttarget")
+             .takes_value(true)
+             .default_value("deadends")
+        )
+        .arg(Arg::with_name("maxloops")
+             .help("(For pipe maps) Maximum number of interconnections to add")
+             .long("maxloops")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("prune")
+             .help("(For pipe maps) Remove dead-end branches shorter than this number of cells (0 = disabled)")
+             .long("prune")
+             .takes_value(true)
+             .default_value("0")
+        )
         .arg(Arg::with_name("iterations")
              .help("(For height map) Iterations in generation")
              .long("iterations")
@@ -85,76 +429,332 @@ fn main() {
              .short("R")
              .default_value("6")
         )
+        .arg(Arg::with_name("algorithm")
+             .help("(For maze map) Maze generation algorithm: backtracker (default), prim, kruskal, wilson")
+             .long("algorithm")
+             .short("a")
+             .default_value("backtracker")
+        )
+        .arg(Arg::with_name("braid")
+             .help("(For maze map) Probability (0.0-1.0) of removing each dead end, 0 gives a perfect maze")
+             .long("braid")
+             .default_value("0")
+        )
+        .arg(Arg::with_name("sample")
+             .help("(For wfc map) Text file with a sample to learn the tiles and adjacency rules from, each character is a tile")
+             .long("sample")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("backtracks")
+             .help("(For wfc map) Maximum number of backtracks before giving up")
+             .long("backtracks")
+             .default_value("1000")
+        )
+        .arg(Arg::with_name("walkers")
+             .help("(For cave map) Number of random walkers")
+             .long("walkers")
+             .default_value("4")
+        )
+        .arg(Arg::with_name("floor")
+             .help("(For cave map) Fraction of the map (0.0-1.0) to carve out")
+             .long("floor")
+             .default_value("0.4")
+        )
+        .arg(Arg::with_name("turn")
+             .help("(For cave map) Probability (0.0-1.0) that a walker changes direction at each step")
+             .long("turn")
+             .default_value("0.3")
+        )
+        .arg(Arg::with_name("roomchance")
+             .help("(For cave map) Probability (0.0-1.0) of stamping a room at a walker's position at each step")
+             .long("roomchance")
+             .default_value("0.01")
+        )
+        .arg(Arg::with_name("roomsize")
+             .help("(For cave map) Maximum width/height of stamped rooms")
+             .long("roomsize")
+             .default_value("5")
+        )
         .arg(Arg::with_name("style")
-             .help("Rendering style. For pipes: thin (default), thick")
+             .help("Rendering style. For pipes and mazes: thin (default), thick")
              .long("style")
              .short("y")
              .default_value("default")
         )
+        .arg(Arg::with_name("symmetry")
+             .help("(For pipe, height and room maps) Symmetry to enforce on the map: none (default), horizontal, vertical, rotate2, rotate4")
+             .long("symmetry")
+             .short("S")
+             .takes_value(true)
+             .default_value("none")
+        )
+        .arg(Arg::with_name("output")
+             .help("Write the map to an image file (.png or .ppm), a web page (.html), a Tiled map (.tmx, or .tmj for JSON, with the tileset written next to it) or save the map itself losslessly (.grid for binary, .csv, or .pgm for height maps) instead of to standard output. SVG and Tiled output are supported for pipe, height and room maps")
+             .long("output")
+             .short("o")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("input")
+             .help("Load the map from a file (as written with --output in the .grid, .csv or .pgm format, or a text map in .txt) instead of generating it, the type determines how it is rendered")
+             .long("input")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("chars")
+             .help("Character table for reading text maps with --input, e.g. '#=0,.=1'. Without it text maps are read as box-drawing pipes")
+             .long("chars")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("blocks")
+             .help("Pack multiple cells into one character when printing to the terminal: halfblock (two cells above each other, keeps colours) or braille (2x4 cells, shows which cells are set)")
+             .long("blocks")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("color")
+             .help("Colours to use in the terminal: auto (default, determined from the NO_COLOR, COLORTERM and TERM environment variables), truecolor, 256, 16 or none")
+             .long("color")
+             .takes_value(true)
+             .default_value("auto")
+        )
+        .arg(Arg::with_name("overlay")
+             .help("Generate a second map of this type (with the same seed and dimensions) and draw it over the map, e.g. pipes over a height map. Only affects output to the terminal, images and web pages")
+             .long("overlay")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("blend")
+             .help("How the overlay is drawn over the map: override (default, whatever the overlay draws replaces the map), text (only the text of the overlay), alpha or alpha:OPACITY (only the background colour of the overlay, mixed with the opacity, 0.5 by default)")
+             .long("blend")
+             .takes_value(true)
+             .default_value("override")
+        )
+        .arg(Arg::with_name("cellsize")
+             .help("Size of a cell in pixels when writing an image or SVG")
+             .long("cellsize")
+             .default_value("8")
+        )
+        .arg(Arg::with_name("config")
+             .help("Load options and generator parameters from a preset file (TOML, or JSON for .json files), options given on the command line take precedence")
+             .long("config")
+             .short("c")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("dumpconfig")
+             .help("Write the effective configuration of the run, which reproduces the map with --config, to a TOML or JSON file (- for standard output, instead of the map)")
+             .long("dumpconfig")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("manifest")
+             .help("Write a manifest of the generated map to a TOML or JSON file: the version, type, parameters, seed, dimensions and a hash of the map, to check later that it can be generated again (see the verify command)")
+             .long("manifest")
+             .takes_value(true)
+        )
+        .arg(Arg::with_name("list")
+             .help("List the available map types, with their render styles and parameters")
+             .long("list")
+        )
         .arg(Arg::with_name("type")
-             .help("type")
+             .help("Map type, see --list")
              .long("type")
              .short("t")
              .takes_value(true)
              .required(true)
              .default_value("pipes")
         )
+        .subcommand(SubCommand::with_name("verify")
+             .about("Generates the map of a manifest (see --manifest) again and checks that it is the same, exits with status 1 if it is not")
+             .arg(Arg::with_name("manifest")
+                  .help("Manifest file (TOML, or JSON for .json files)")
+                  .required(true)
+             )
+        )
+        .subcommand(SubCommand::with_name("batch")
+             .about("Generates many maps in parallel into a directory, over a range of seeds and a grid of parameter values. Every map gets its own directory with the map (.grid), an image and a manifest, summary.csv lists the metrics of all maps. The type, size, style and other parameters are taken from the options before the command")
+             .arg(Arg::with_name("directory")
+                  .help("Output directory")
+                  .required(true)
+             )
+             .arg(Arg::with_name("seeds")
+                  .help("Seeds and ranges of seeds (the end is excluded), e.g. 1..1000 or 1,2,castle-42")
+                  .long("seeds")
+                  .takes_value(true)
+                  .required(true)
+             )
+             .arg(Arg::with_name("vary")
+                  .help("A parameter of the map type and the values it takes, e.g. rooms=4|6|8, maps are generated for every combination of values (and every seed). Can be repeated")
+                  .long("vary")
+                  .takes_value(true)
+                  .multiple(true)
+                  .number_of_values(1)
+             )
+             .arg(Arg::with_name("threads")
+                  .help("Number of threads (default: the number of CPUs)")
+                  .long("threads")
+                  .takes_value(true)
+             )
+        )
         .get_matches();
 
+    let registry = Registry::default();
+    if let Some(argmatches) = argmatches.subcommand_matches("verify") {
+        if !verify(&registry, argmatches.value_of("manifest").unwrap()) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    let config = match argmatches.value_of("config") {
+        Some(filename) => match Config::load(filename).and_then(|config| config.check(&registry, CONFIGOPTIONS).map(|_| config)) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Unable to load {}: {}", filename, err);
+                std::process::exit(1);
+            }
+        },
+        None => Config::new(),
+    };
+    let batchmatches = argmatches.subcommand_matches("batch").cloned();
+    let options = Options { argmatches, config };
+    let colourmode = colourmode(&options);
+
+    if let Some(batchmatches) = batchmatches {
+        if !batch(&registry, &options, &batchmatches) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if options.is_present("list") {
+        list(&registry);
+        return;
+    }
+
     let mut looptime: u64 = 0;
-    if argmatches.is_present("loop") {
-        looptime = argmatches.value_of("loop").unwrap().parse::<u64>().expect("Invalid loop time");
+    if options.is_present("loop") {
+        looptime = options.value("loop").unwrap().parse::<u64>().expect("Invalid loop time");
+    }
+
+    if options.is_present("view") {
+        let maptype = options.value("type").unwrap();
+        if registry.get(maptype).is_none() {
+            eprintln!("No such type, see --list for the available types");
+            std::process::exit(1);
+        }
+        let mut seed: u64 = Seed::parse(options.value("seed").unwrap()).0;
+        if seed == 0 {
+            seed = rand::random::<u64>();
+        }
+        let settings = ViewerSettings {
+            maptype: maptype.to_string(),
+            style: options.value("style").unwrap().to_string(),
+            seed,
+            iterations: options.parameter(maptype, "iterations").unwrap().parse::<usize>().expect("Invalid number of iterations"),
+            rooms: options.parameter(maptype, "rooms").unwrap().parse::<usize>().expect("Invalid number of rooms"),
+            backboneseeds: options.parameter(maptype, "backboneseeds").unwrap().parse::<u16>().expect("Invalid number of backbone seeds"),
+        };
+        //with --loop the viewer starts in auto mode, picking a new seed every interval
+        let interval = time::Duration::from_millis(if looptime > 0 { looptime } else { 1000 });
+        let types: Vec<(&str, &[&str])> = registry.iter().map(|generator| (generator.name(), generator.styles())).collect();
+        let settings = viewer::view(settings, &types, colourmode, interval, looptime > 0, |settings| {
+            let mut overrides = Overrides::new();
+            overrides.insert("style", settings.style.clone());
+            overrides.insert("iterations", settings.iterations.to_string());
+            overrides.insert("rooms", settings.rooms.to_string());
+            overrides.insert("backboneseeds", settings.backboneseeds.to_string());
+            let (renderedgrid, _, _, _) = generate(&registry, &settings.maptype, &options, &overrides, settings.seed, options.value("input"))?;
+            addoverlay(&registry, renderedgrid, &options, &overrides, settings.seed)
+        });
+        let settings = match settings {
+            Ok(settings) => settings,
+            Err(err) => {
+                eprintln!("Unable to run the viewer: {}", err);
+                std::process::exit(1);
+            }
+        };
+        //so the last map can be generated again
+        println!("--type {} --style {} --seed {} --iterations {} --rooms {} --backboneseeds {}",
+                 settings.maptype, settings.style, settings.seed, settings.iterations, settings.rooms, settings.backboneseeds);
+        return;
     }
 
     loop {
 
-        let mut seed: u64 = argmatches.value_of("seed").unwrap().parse::<u64>().expect("Invalid seed");
+        let mut seed: u64 = Seed::parse(options.value("seed").unwrap()).0;
         if seed == 0 {
             seed = rand::random::<u64>();
         } else {
             //looping makes no sense if we have a specified seed
             looptime = 0;
         }
-        let width =  argmatches.value_of("width").unwrap().parse::<usize>().expect("Invalid width") as usize;
-        let height = argmatches.value_of("height").unwrap().parse::<usize>().expect("Invalid height") as usize;
-        match argmatches.value_of("type").unwrap() {
-            "pipes" => {
-                let regularseeds: Option<Vec<&str>>= argmatches.value_of("regularseeds").map(|regularseeds: &str| {
-                                        regularseeds.split_terminator(',').collect()
-                                    });
-                let regularseeds: Vec<u16> = regularseeds.unwrap().iter().map(|x:&&str| { x.parse::<u16>().unwrap() } ).collect();
-                //using a <Type as Trait> construction: https://doc.rust-lang.org/book/ch19-03-advanced-traits.html
-                // to construct the grid
-                let grid: Grid<u16,u8> = <Grid<u16,u8> as PipeGrid<u16,u8>>::generate(width as u16,height as u16, seed, PipeGridProperties {
-                    backboneseeds: argmatches.value_of("backboneseeds").unwrap().parse::<u16>().unwrap() as u16,
-                    regularseeds: regularseeds,
-                    interconnect: argmatches.is_present("interconnect"),
-                });
-                println!("{}", PipeGrid::render(&grid, match argmatches.value_of("style").unwrap() {
-                    "thick" => PipeRenderStyle::Thick,
-                    _ => PipeRenderStyle::Thin
-                }));
-            },
-            "height" => {
-                let grid: Grid<u16,u8> = <Grid<u16,u8> as HeightGrid<u16,u8>>::generate(width as u16, height as u16, seed, HeightGridProperties {
-                    iterations: argmatches.value_of("iterations").unwrap().parse::<usize>().unwrap() as usize,
-                });
-                println!("{}", HeightGrid::render(&grid, match argmatches.value_of("style").unwrap() {
-                    "heatmap" => HeightRenderStyle::HeatMap,
-                    "terrain" => HeightRenderStyle::Terrain,
-                    _ => HeightRenderStyle::Simple
-                }));
-            },
-            "rooms" => {
-                let grid: Grid<u16,u8> = <Grid<u16,u8> as RoomGrid<u16,u8>>::generate(width as u16, height as u16, seed, RoomGridProperties {
-                    rooms: argmatches.value_of("rooms").unwrap().parse::<usize>().unwrap() as usize,
-                });
-                println!("{}", RoomGrid::render(&grid));
-            },
-            _ => {
-                eprintln!("No such type");
+        if let Some(filename) = options.value("dumpconfig") {
+            let config = effectiveconfig(&registry, &options, seed);
+            if filename == "-" {
+                print!("{}", config.to_toml());
                 break;
             }
+            config.save(filename).expect("Unable to write configuration");
+        }
+        let cellsize = options.value("cellsize").unwrap().parse::<usize>().expect("Invalid cell size");
+        let svgoutput = options.value("output").is_some_and(|filename| filename.ends_with(".svg"));
+        let tiledoutput = options.value("output").is_some_and(|filename| filename.ends_with(".tmx") || filename.ends_with(".tmj"));
+        let maptype = options.value("type").unwrap();
+        let (renderedgrid, svg, tiledmap, grid): Outputs = match generate(&registry, maptype, &options, &Overrides::new(), seed, options.value("input")) {
+            Ok(outputs) => outputs,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        };
+        let renderedgrid = match addoverlay(&registry, renderedgrid, &options, &Overrides::new(), seed) {
+            Ok(renderedgrid) => renderedgrid,
+            Err(message) => {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+        };
+        if let Some(filename) = options.value("manifest") {
+            if options.value("input").is_some() {
+                eprintln!("A manifest can only be written for generated maps, not for maps loaded with --input");
+                std::process::exit(1);
+            }
+            let generator = registry.get(maptype).unwrap();
+            let manifest = Manifest::new(&registry, maptype, &parameters(generator, &options, &Overrides::new()), seed, &grid).expect("Unable to describe map");
+            manifest.save(filename).expect("Unable to write manifest");
+        }
+        if svgoutput {
+            if let Some(svg) = svg {
+                svg.save(options.value("output").unwrap()).expect("Unable to write SVG");
+            } else {
+                eprintln!("SVG output is not supported for this type");
+                std::process::exit(1);
+            }
+        } else if tiledoutput {
+            if let Some(tiledmap) = tiledmap {
+                tiledmap.save(options.value("output").unwrap()).expect("Unable to write Tiled map");
+            } else {
+                eprintln!("Tiled output is not supported for this type");
+                std::process::exit(1);
+            }
+        } else if let Some(filename) = options.value("output").filter(|filename| [".grid",".csv",".pgm"].iter().any(|extension| filename.ends_with(extension))) {
+            grid.save(filename).expect("Unable to save map");
+        } else if let Some(filename) = options.value("output").filter(|filename| filename.ends_with(".html")) {
+            let html = renderedgrid.html(&format!("{} map, seed {}", maptype, seed), |point| registry.get(maptype).unwrap().describe(&grid, point));
+            fs::write(filename, html).expect("Unable to write HTML");
+        } else if let Some(filename) = options.value("output") {
+            renderedgrid.rasterize(cellsize).save(filename).expect("Unable to write image");
+        } else {
+            if options.is_present("animate") {
+                //the final map replaces the last frame
+                print!("\x1b[H\x1b[J");
+            }
+            match options.value("blocks") {
+                Some("halfblock") => print!("{}", renderedgrid.halfblocks().paint(colourmode)),
+                Some("braille") => {
+                    let mut layer: Grid<u16,bool> = Grid::new(grid.width(), grid.height());
+                    for (point, value) in grid.iter() {
+                        layer.set(&point, *value != 0);
+                    }
+                    print!("{}", layer.braille(None).paint(colourmode));
+                },
+                _ => print!("{}", renderedgrid.paint(colourmode)),
+            }
         }
         if looptime > 0 {
             //escape sequence to clear screen
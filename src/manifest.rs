@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use crate::grid::{Grid,GenericGrid};
+use crate::config::Config;
+use crate::error::Error;
+use crate::generator::{Registry,Parameters,withdefaults};
+use crate::serialization::SerializableGrid;
+
+///Everything needed to generate a map again, and a hash of the map to check that the same map
+///comes out. Manifests are stored like configuration files (TOML, or JSON for `.json` files):
+///
+///```toml
+///generator = "pipes"
+///hash = "5a0e31c7"
+///height = 30
+///seed = 42
+///version = "0.1.0"
+///width = 80
+///
+///[generators.pipes]
+///backboneseeds = 20
+///regularseeds = [40, 40, 60]
+///```
+#[derive(Debug,Clone,PartialEq)]
+pub struct Manifest {
+    ///the version of the crate the map was generated with
+    pub version: String,
+    pub generator: String,
+    ///all parameters of the generator, including the defaults
+    pub parameters: BTreeMap<String,String>,
+    pub seed: u64,
+    pub width: u16,
+    pub height: u16,
+    ///see `gridhash()`
+    pub hash: String,
+}
+
+///The hash of a map: the CRC32 checksum of its binary serialization (the one that ends the
+///serialization), in hexadecimal
+pub fn gridhash(grid: &Grid<u16,u8>) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    grid.write_binary(&mut buffer).expect("Unable to serialize grid");
+    let checksum = &buffer[buffer.len() - 4..];
+    format!("{:08x}", u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]))
+}
+
+impl Manifest {
+    ///Describes a map generated by the given generator of the registry
+    pub fn new(registry: &Registry, generator: &str, parameters: &Parameters, seed: u64, grid: &Grid<u16,u8>) -> Result<Manifest, Error> {
+        let generator = registry.get(generator).ok_or_else(|| Error::InvalidParameter(format!("No such generator: {}", generator)))?;
+        Ok(Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            generator: generator.name().to_string(),
+            parameters: withdefaults(parameters, &generator.parameters()).into_iter().collect(),
+            seed,
+            width: grid.width(),
+            height: grid.height(),
+            hash: gridhash(grid),
+        })
+    }
+
+    ///Generates the map described by the manifest again
+    pub fn regenerate(&self, registry: &Registry) -> Result<Grid<u16,u8>, Error> {
+        let generator = registry.get(&self.generator).ok_or_else(|| Error::InvalidParameter(format!("No such generator: {}", self.generator)))?;
+        let parameters: Parameters = self.parameters.clone().into_iter().collect();
+        generator.generate(self.width, self.height, self.seed, &parameters, &mut |_, _| {})
+    }
+
+    ///Generates the map again and returns the hash of the result, which matches the hash of the
+    ///manifest if the map is the same
+    pub fn verify(&self, registry: &Registry) -> Result<String, Error> {
+        Ok(gridhash(&self.regenerate(registry)?))
+    }
+
+    pub fn to_config(&self) -> Config {
+        let mut config = Config::new();
+        config.options.insert("version".to_string(), self.version.clone());
+        config.options.insert("generator".to_string(), self.generator.clone());
+        config.options.insert("seed".to_string(), self.seed.to_string());
+        config.options.insert("width".to_string(), self.width.to_string());
+        config.options.insert("height".to_string(), self.height.to_string());
+        config.options.insert("hash".to_string(), self.hash.clone());
+        config.generators.insert(self.generator.clone(), self.parameters.clone());
+        config
+    }
+
+    pub fn from_config(config: &Config) -> Result<Manifest, Error> {
+        let generator = field(config, "generator")?.to_string();
+        Ok(Manifest {
+            version: field(config, "version")?.to_string(),
+            parameters: config.generators.get(&generator).cloned().unwrap_or_default(),
+            generator,
+            seed: number(config, "seed")?,
+            width: number(config, "width")?,
+            height: number(config, "height")?,
+            hash: field(config, "hash")?.to_string(),
+        })
+    }
+
+    ///Loads a manifest, JSON if the file name ends in `.json`, TOML otherwise
+    pub fn load(filename: &str) -> Result<Manifest, Error> {
+        Manifest::from_config(&Config::load(filename)?)
+    }
+
+    ///Writes the manifest, as JSON if the file name ends in `.json`, TOML otherwise
+    pub fn save(&self, filename: &str) -> Result<(), Error> {
+        self.to_config().save(filename)
+    }
+}
+
+fn field<'a>(config: &'a Config, name: &str) -> Result<&'a str, Error> {
+    config.option(name).ok_or_else(|| Error::Parse(format!("Invalid manifest: missing {}", name)))
+}
+
+fn number<T: std::str::FromStr>(config: &Config, name: &str) -> Result<T, Error> {
+    field(config, name)?.parse().map_err(|_| Error::Parse(format!("Invalid manifest: invalid {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    fn grid() -> Grid<u16,u8> {
+        let mut grid: Grid<u16,u8> = Grid::new(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                grid.set(&Point(x,y), (x * 10 + y) as u8);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn fixed_hash() {
+        //the hash must not change between versions or platforms, or manifests no longer verify
+        assert_eq!(gridhash(&grid()), "41969c9f");
+    }
+}
@@ -0,0 +1,370 @@
+use rand::{SeedableRng,Rng};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+use std::cmp::PartialEq;
+use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,CheckedAdd,CheckedSub};
+use std::str::FromStr;
+
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::pipegrid::{PipeRenderStyle,pipechar,renderstyle};
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter};
+
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum MazeAlgorithm {
+    ///Randomized depth-first search, gives long winding corridors with few branches
+    #[default]
+    RecursiveBacktracker,
+    ///Randomized Prim's algorithm, gives many short dead ends
+    Prim,
+    ///Randomized Kruskal's algorithm
+    Kruskal,
+    ///Wilson's algorithm (loop-erased random walks), gives an unbiased uniform spanning tree
+    Wilson,
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct MazeGridProperties {
+    ///the algorithm used to generate the perfect maze
+    pub algorithm: MazeAlgorithm,
+
+    ///probability (0.0 - 1.0) that a dead end is removed by opening one of its walls, 0.0 keeps the maze perfect
+    pub braid: f64,
+}
+
+///The maze is built on a lattice of cells at the even coordinates of the grid, the grid cells in
+///between two lattice cells are the walls that may be opened up
+struct Lattice {
+    width: usize,
+    height: usize,
+}
+
+impl Lattice {
+    fn new(width: usize, height: usize) -> Lattice {
+        Lattice {
+            width: width.div_ceil(2),
+            height: height.div_ceil(2),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn neighbours(&self, cell: usize) -> Vec<usize> {
+        let (x, y) = (cell % self.width, cell / self.width);
+        let mut neighbours = Vec::new();
+        if y > 0 { neighbours.push(cell - self.width); }
+        if x + 1 < self.width { neighbours.push(cell + 1); }
+        if y + 1 < self.height { neighbours.push(cell + self.width); }
+        if x > 0 { neighbours.push(cell - 1); }
+        neighbours
+    }
+
+    ///All pairs of adjacent lattice cells, i.e. all walls
+    fn edges(&self) -> Vec<(usize,usize)> {
+        let mut edges = Vec::new();
+        for cell in 0..self.size() {
+            for neighbour in self.neighbours(cell) {
+                if cell < neighbour {
+                    edges.push((cell, neighbour));
+                }
+            }
+        }
+        edges
+    }
+
+    fn point<ScaleType>(&self, cell: usize) -> Point<ScaleType> where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+        Point::new_usize((cell % self.width) * 2, (cell / self.width) * 2)
+    }
+
+    ///The grid point of the wall between two adjacent lattice cells
+    fn wall<ScaleType>(&self, cell: usize, cell2: usize) -> Point<ScaleType> where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+        Point::new_usize((cell % self.width) + (cell2 % self.width), (cell / self.width) + (cell2 / self.width))
+    }
+
+    fn backtracker<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(usize,usize)> {
+        let mut passages = Vec::new();
+        let mut visited = vec![false; self.size()];
+        let start = rng.gen_range(0, self.size());
+        visited[start] = true;
+        let mut stack = vec![start];
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<usize> = self.neighbours(cell).into_iter().filter(|neighbour| !visited[*neighbour]).collect();
+            if let Some(&next) = unvisited.choose(rng) {
+                visited[next] = true;
+                passages.push((cell, next));
+                stack.push(next);
+            } else {
+                stack.pop();
+            }
+        }
+        passages
+    }
+
+    fn prim<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(usize,usize)> {
+        let mut passages = Vec::new();
+        let mut visited = vec![false; self.size()];
+        let start = rng.gen_range(0, self.size());
+        visited[start] = true;
+        let mut frontier: Vec<(usize,usize)> = self.neighbours(start).into_iter().map(|neighbour| (start, neighbour)).collect();
+        while !frontier.is_empty() {
+            let (cell, next) = frontier.swap_remove(rng.gen_range(0, frontier.len()));
+            if !visited[next] {
+                visited[next] = true;
+                passages.push((cell, next));
+                for neighbour in self.neighbours(next) {
+                    if !visited[neighbour] {
+                        frontier.push((next, neighbour));
+                    }
+                }
+            }
+        }
+        passages
+    }
+
+    fn kruskal<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(usize,usize)> {
+        let mut passages = Vec::new();
+        //union-find forest over all cells
+        let mut parents: Vec<usize> = (0..self.size()).collect();
+        fn root(parents: &mut [usize], mut cell: usize) -> usize {
+            while parents[cell] != cell {
+                parents[cell] = parents[parents[cell]];
+                cell = parents[cell];
+            }
+            cell
+        }
+        let mut edges = self.edges();
+        edges.shuffle(rng);
+        for (cell, cell2) in edges {
+            let root1 = root(&mut parents, cell);
+            let root2 = root(&mut parents, cell2);
+            if root1 != root2 {
+                parents[root1] = root2;
+                passages.push((cell, cell2));
+            }
+        }
+        passages
+    }
+
+    fn wilson<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(usize,usize)> {
+        let mut passages = Vec::new();
+        let mut inmaze = vec![false; self.size()];
+        inmaze[rng.gen_range(0, self.size())] = true;
+        //the direction we last left each cell in during a walk, revisiting a cell overwrites it,
+        //which erases the loop
+        let mut next: Vec<usize> = vec![0; self.size()];
+        for start in 0..self.size() {
+            let mut cell = start;
+            while !inmaze[cell] {
+                next[cell] = *self.neighbours(cell).choose(rng).expect("Lattice cell has no neighbours");
+                cell = next[cell];
+            }
+            cell = start;
+            while !inmaze[cell] {
+                inmaze[cell] = true;
+                passages.push((cell, next[cell]));
+                cell = next[cell];
+            }
+        }
+        passages
+    }
+
+    ///Remove dead ends by opening additional walls, each dead end is removed with the given probability
+    fn braid<R: Rng + ?Sized>(&self, rng: &mut R, passages: &mut Vec<(usize,usize)>, probability: f64) {
+        let mut connections: Vec<Vec<usize>> = vec![Vec::new(); self.size()];
+        for (cell, cell2) in passages.iter() {
+            connections[*cell].push(*cell2);
+            connections[*cell2].push(*cell);
+        }
+        let mut cells: Vec<usize> = (0..self.size()).collect();
+        cells.shuffle(rng);
+        for cell in cells {
+            if connections[cell].len() == 1 && rng.gen::<f64>() < probability {
+                let closed: Vec<usize> = self.neighbours(cell).into_iter().filter(|neighbour| !connections[cell].contains(neighbour)).collect();
+                //prefer connecting to another dead end, that removes two at once
+                let deadends: Vec<usize> = closed.iter().copied().filter(|neighbour| connections[*neighbour].len() == 1).collect();
+                let candidates = if deadends.is_empty() { closed } else { deadends };
+                if let Some(&cell2) = candidates.choose(rng) {
+                    connections[cell].push(cell2);
+                    connections[cell2].push(cell);
+                    passages.push((cell, cell2));
+                }
+            }
+        }
+    }
+}
+
+pub trait MazeGrid<ScaleType, ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: MazeGridProperties) -> Grid<ScaleType,ValueType>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: MazeGridProperties) -> Grid<ScaleType,ValueType>;
+    fn render(&self, renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell>;
+    fn rendercell(&self, point: &Point<ScaleType>, renderstyle: PipeRenderStyle) -> RenderedTextCell;
+}
+
+impl<ScaleType,ValueType> MazeGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    ///Generates a maze, passages are set to one and walls are zero
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: MazeGridProperties) -> Grid<ScaleType,ValueType> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties)
+    }
+
+    ///Generates a maze like `generate()`, with a random number generator of the given type seeded from
+    ///`seed`
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: MazeGridProperties) -> Grid<ScaleType,ValueType> {
+        let mut rng = R::seed_from_u64(seed);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width, height);
+        let lattice = Lattice::new(grid.width_as_usize(), grid.height_as_usize());
+        if lattice.size() == 0 {
+            return grid;
+        }
+
+        let mut passages = match properties.algorithm {
+            MazeAlgorithm::RecursiveBacktracker => lattice.backtracker(&mut rng),
+            MazeAlgorithm::Prim => lattice.prim(&mut rng),
+            MazeAlgorithm::Kruskal => lattice.kruskal(&mut rng),
+            MazeAlgorithm::Wilson => lattice.wilson(&mut rng),
+        };
+        if properties.braid > 0.0 {
+            lattice.braid(&mut rng, &mut passages, properties.braid);
+        }
+
+        for cell in 0..lattice.size() {
+            grid.set(&lattice.point(cell), ValueType::one());
+        }
+        for (cell, cell2) in passages {
+            grid.set(&lattice.wall(cell, cell2), ValueType::one());
+        }
+        grid
+    }
+
+    fn render(&self, renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell> {
+        let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
+        for point in self.rectangle().iter() {
+            renderedgrid.set(&point,  MazeGrid::rendercell(self, &point, renderstyle) );
+        }
+        renderedgrid
+    }
+
+    fn rendercell(&self, point: &Point<ScaleType>, renderstyle: PipeRenderStyle) -> RenderedTextCell {
+        let chr: char = if self[point] == ValueType::zero() {
+            ' '
+        } else {
+           let (hasnorth, haseast, hassouth, haswest) = self.hasneighbours(point);
+           match renderstyle {
+               PipeRenderStyle::Thick => '█',
+               PipeRenderStyle::Thin => pipechar(hasnorth, haseast, hassouth, haswest, false),
+           }
+        };
+        RenderedTextCell {
+            background_colour: None,
+            foreground_colour: None,
+            text: Some(chr.to_string())
+        }
+    }
+}
+
+impl FromStr for MazeAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "backtracker" => Ok(MazeAlgorithm::RecursiveBacktracker),
+            "prim" => Ok(MazeAlgorithm::Prim),
+            "kruskal" => Ok(MazeAlgorithm::Kruskal),
+            "wilson" => Ok(MazeAlgorithm::Wilson),
+            _ => Err(format!("No such maze algorithm: {}", s)),
+        }
+    }
+}
+
+///Generates mazes through `MazeGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct MazeGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for MazeGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = MazeGridProperties;
+
+    fn name(&self) -> &'static str {
+        "maze"
+    }
+
+    fn description(&self) -> &'static str {
+        "A perfect maze, optionally braided to remove dead ends"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("algorithm", "Maze generation algorithm: backtracker, prim, kruskal, wilson", Some("backtracker")),
+            ParameterInfo::new("braid", "Probability (0.0-1.0) of removing each dead end, 0 gives a perfect maze", Some("0")),
+        )
+    }
+
+    fn styles(&self) -> &'static [&'static str] {
+        &["thin", "thick"]
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<MazeGridProperties, Error> {
+        Ok(MazeGridProperties {
+            algorithm: parameter(parameters, "algorithm")?,
+            braid: parameter(parameters, "braid")?,
+        })
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &MazeGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as MazeGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone()))
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, style: &str, _properties: &MazeGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        MazeGrid::render(grid, renderstyle(style))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///Checks that a perfect maze is a spanning tree of the lattice: every cell is reachable from
+    ///the first one and there is exactly one passage less than there are cells
+    fn checkmaze(algorithm: MazeAlgorithm, width: u16, height: u16, seed: u64) {
+        let grid = <Grid<u16,u8> as MazeGrid<u16,u8>>::generate(width, height, seed, MazeGridProperties { algorithm, braid: 0.0 });
+        let lattice = Lattice::new(width as usize, height as usize);
+        let mut reached: Grid<u16,bool> = Grid::new(width, height);
+        let mut stack = vec![Point(0, 0)];
+        reached.set(&Point(0, 0), true);
+        while let Some(point) = stack.pop() {
+            for neighbour in grid.getneighbours(&point) {
+                if grid[&neighbour] != 0 && !reached[&neighbour] {
+                    reached.set(&neighbour, true);
+                    stack.push(neighbour);
+                }
+            }
+        }
+        for cell in 0..lattice.size() {
+            assert!(reached[&lattice.point(cell)], "{:?} with seed {}: cell {} is not reachable", algorithm, seed, cell);
+        }
+        let passages = grid.iter().filter(|(point, value)| **value != 0 && (point.x() % 2 == 1 || point.y() % 2 == 1)).count();
+        assert_eq!(passages, lattice.size() - 1, "{:?} with seed {}", algorithm, seed);
+    }
+
+    #[test]
+    fn mazes_are_spanning_trees() {
+        for algorithm in [MazeAlgorithm::RecursiveBacktracker, MazeAlgorithm::Prim, MazeAlgorithm::Kruskal, MazeAlgorithm::Wilson].iter() {
+            for seed in 1..4 {
+                checkmaze(*algorithm, 21, 11, seed);
+                checkmaze(*algorithm, 20, 10, seed);
+            }
+        }
+    }
+}
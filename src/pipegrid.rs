@@ -1,14 +1,24 @@
 use rand::{SeedableRng,Rng};
 use rand_pcg::Pcg32;
-use std::cmp::{min,PartialEq,Eq};
-use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,range,CheckedAdd,CheckedSub};
+use std::cmp::PartialEq;
+use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,CheckedAdd,CheckedSub};
 
-use crate::common::{Distance,Direction,Volume};
+use crate::common::{Direction,Distance};
 use crate::point::Point;
-use crate::rectangle::Rectangle;
 use crate::grid::{Grid,GenericGrid,NumericGrid,RenderedTextCell};
+use crate::symmetry::Symmetry;
+use crate::seed::Seed;
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter,optionalparameter,checkdimensions,describesize};
+use crate::events::{GenerationEvent,Observer};
+use crate::svg::{Svg,Segment};
+use crate::tiled::{TiledMap,TiledObject,ObjectGroup};
+use crate::image::{DEFAULT_BACKGROUND,DEFAULT_FOREGROUND};
+use crate::textmap::{connectionmask,connectionarms};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
-#[derive(Debug,Default)]
+#[derive(Debug,Clone,Default)]
 pub struct PipeGridProperties {
     ///initial backbone points
     pub backboneseeds: u16,
@@ -18,6 +28,117 @@ pub struct PipeGridProperties {
 
     ///prune dead-ends to a large extent by interconnecting them
     pub interconnect: bool,
+
+    ///what dead ends are connected to when interconnecting
+    pub interconnect_target: InterconnectTarget,
+
+    ///maximum number of interconnections (loops) to add, None for no limit
+    pub maxloops: Option<usize>,
+
+    ///remove dead-end branches shorter than this number of cells (0 = no pruning)
+    pub prune: usize,
+
+    ///symmetry to enforce on the map
+    pub symmetry: Symmetry,
+}
+
+impl PipeGridProperties {
+    ///Starts building properties for a map of the given size, with the defaults of the `pipes`
+    ///generator
+    pub fn builder(width: usize, height: usize) -> PipeGridPropertiesBuilder {
+        PipeGridPropertiesBuilder {
+            width,
+            height,
+            properties: PipeGridProperties {
+                backboneseeds: 20,
+                regularseeds: vec!(40,40,60),
+                ..PipeGridProperties::default()
+            },
+        }
+    }
+
+    ///Checks that the properties can be used for a map of the given size: every seed needs a
+    ///cell of its own and the values of all iterations must stay below `INTERCONNECT`
+    pub fn validate(&self, width: usize, height: usize) -> Result<(), Error> {
+        checkdimensions(width, height)?;
+        let (sectorwidth, sectorheight) = self.symmetry.sector(width, height);
+        let seeds = self.backboneseeds as usize + self.regularseeds.iter().map(|seeds| *seeds as usize).sum::<usize>();
+        if seeds > sectorwidth * sectorheight {
+            return Err(Error::InvalidParameter(format!("{} seeds ({} backbone, {} regular) do not fit in the {} cells of a {}",
+                       seeds, self.backboneseeds, seeds - self.backboneseeds as usize, sectorwidth * sectorheight, describesize(width, height, self.symmetry))));
+        }
+        if self.regularseeds.len() + 3 >= INTERCONNECT as usize {
+            return Err(Error::InvalidParameter(format!("At most {} iterations of regular seeds are supported, got {}",
+                       INTERCONNECT as usize - 4, self.regularseeds.len())));
+        }
+        Ok(())
+    }
+}
+
+///Builds `PipeGridProperties` that are checked against the size of the map, see
+///`PipeGridProperties::builder()`
+#[derive(Debug,Clone)]
+pub struct PipeGridPropertiesBuilder {
+    width: usize,
+    height: usize,
+    properties: PipeGridProperties,
+}
+
+impl PipeGridPropertiesBuilder {
+    pub fn backboneseeds(mut self, backboneseeds: u16) -> Self {
+        self.properties.backboneseeds = backboneseeds;
+        self
+    }
+
+    pub fn regularseeds(mut self, regularseeds: Vec<u16>) -> Self {
+        self.properties.regularseeds = regularseeds;
+        self
+    }
+
+    pub fn interconnect(mut self, interconnect: bool) -> Self {
+        self.properties.interconnect = interconnect;
+        self
+    }
+
+    pub fn interconnect_target(mut self, interconnect_target: InterconnectTarget) -> Self {
+        self.properties.interconnect_target = interconnect_target;
+        self
+    }
+
+    pub fn maxloops(mut self, maxloops: Option<usize>) -> Self {
+        self.properties.maxloops = maxloops;
+        self
+    }
+
+    pub fn prune(mut self, prune: usize) -> Self {
+        self.properties.prune = prune;
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.properties.symmetry = symmetry;
+        self
+    }
+
+    ///Returns the properties, or a description of why they do not fit the map
+    pub fn build(self) -> Result<PipeGridProperties, Error> {
+        self.properties.validate(self.width, self.height)?;
+        Ok(self.properties)
+    }
+}
+
+///Cell value of interconnections. Backbone seeds hold 1 and the backbone paths 2, the seeds of
+///the first iteration of regular seeds hold 3 and the paths grown from them 4, the seeds of the
+///second iteration 4 and their paths 5, etc.
+pub const INTERCONNECT: u8 = 99;
+
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub enum InterconnectTarget {
+    ///connect each dead end to the nearest other dead end (or former dead end)
+    #[default]
+    DeadEnds,
+    ///connect each dead end to the nearest pipe cell that is not part of its own branch
+    Pipes,
 }
 
 #[derive(Debug,Clone,Copy)]
@@ -26,13 +147,78 @@ pub enum PipeRenderStyle {
     Thick
 }
 
+///Returns the box-drawing character for a pipe cell given which of its neighbours (north, east,
+///south, west) are connected, heavy lines are used for the backbone. A cell without connections
+///is drawn as a closed square.
+pub fn pipechar(hasnorth: bool, haseast: bool, hassouth: bool, haswest: bool, isbackbone: bool) -> char {
+    match (hasnorth, haseast, hassouth, haswest, isbackbone) {
+        (true,true,true,true, false) => '┼',
+        (true,true,true,true, true) => '╋',
+        (true,true,true,false, false) => '├',
+        (true,true,true,false, true) => '┣',
+        (false,true,true,true, false) => '┬',
+        (false,true,true,true, true) => '┳',
+        (true,false,true,true, false) => '┤',
+        (true,false,true,true, true) => '┫',
+        (true,true,false,true, false) => '┴',
+        (true,true,false,true, true) => '┻',
+        (true,true,false,false, false) => '└',
+        (true,true,false,false, true) => '┗',
+        (true,false,true,false, false) => '│',
+        (true,false,true,false, true) => '┃',
+        (true,false,false,true, false) => '┘',
+        (true,false,false,true, true) => '┛',
+        (false,true,true,false, false) => '┌',
+        (false,true,true,false, true) => '┏',
+        (false,true,false,true, false) => '─',
+        (false,true,false,true, true) => '━',
+        (false,false,true,true, false) => '┐',
+        (false,false,true,true, true) => '┓',
+        (true,false,false,false, false) => '╵',
+        (true,false,false,false, true) => '╹',
+        (false,true,false,false, false) => '╶',
+        (false,true,false,false, true) => '╺',
+        (false,false,true,false, false) => '╷',
+        (false,false,true,false, true) => '╻',
+        (false,false,false,true, false) => '╴',
+        (false,false,false,true, true) => '╸',
+        (false,false,false,false, false) => '□',
+        (false,false,false,false, true) => '■',
+    }
+}
+
+///The inverse of `pipechar()`: returns which neighbours (north, east, south, west) a box-drawing
+///glyph connects to and whether it is heavy (backbone)
+pub fn pipearms(glyph: char) -> Option<([bool;4], bool)> {
+    for i in 0..32 {
+        let arms = [i & 1 != 0, i & 2 != 0, i & 4 != 0, i & 8 != 0];
+        let heavy = i & 16 != 0;
+        if pipechar(arms[0], arms[1], arms[2], arms[3], heavy) == glyph {
+            return Some((arms, heavy));
+        }
+    }
+    None
+}
+
 pub trait PipeGrid<ScaleType, ValueType> where
     ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
     ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
 
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties) -> Grid<ScaleType,ValueType>;
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
     fn render(&self, renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell>;
+    fn render_with_connections(&self, connections: &Grid<ScaleType,u8>, renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell>;
     fn rendercell(&self, point: &Point<ScaleType> ,renderstyle: PipeRenderStyle) -> RenderedTextCell;
+    fn connections(&self) -> Grid<ScaleType,u8>;
+    fn deadends(&self) -> Vec<Point<ScaleType>>;
+    fn deadends_with_connections(&self, connections: &Grid<ScaleType,u8>) -> Vec<Point<ScaleType>>;
+    fn deadendbranch(&self, point: &Point<ScaleType>) -> Option<(Vec<Point<ScaleType>>, Point<ScaleType>)>;
+    fn prune(&mut self, minlength: usize) -> usize;
+    fn tier(&self, point: &Point<ScaleType>) -> usize;
+    fn isseed(&self, point: &Point<ScaleType>) -> bool;
+    fn svg(&self, cellsize: f64) -> Svg;
+    fn tiled(&self, tilesize: usize) -> TiledMap;
 }
 
 
@@ -42,13 +228,31 @@ impl<ScaleType,ValueType> PipeGrid<ScaleType,ValueType> for Grid<ScaleType,Value
 
     ///Generates the network (a planar graph), with a backbone
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties) -> Grid<ScaleType,ValueType> {
-        let mut rng = Pcg32::seed_from_u64(seed);
-        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width, height);
+        Self::generate_with_events(width, height, seed, properties, &mut |_, _| {})
+    }
+
+    ///Generates the network like `generate()`, reporting every path that is drawn to the observer
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties, observer)
+    }
+
+    ///Generates the network like `generate_with_events()`, with random number generators of the given type
+    ///(one per phase, seeded from `seed`)
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: PipeGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        //every phase has its own random number generator, so they do not influence each other
+        let seed = Seed::from(seed);
+        let mut rng: R = seed.rng("backbone");
+        //with symmetry we only generate a single sector
+        let (sectorwidth, sectorheight) = properties.symmetry.sector(width, height);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(sectorwidth, sectorheight);
 
         let mut backboneseeds: Vec<Point<ScaleType>> = Vec::new();
         //add initial backbone nodes
         for _ in 0..properties.backboneseeds {
-            let point = Point::random(&mut rng, &grid.rectangle());
+            let point = match Point::random(&mut rng, &grid.rectangle()) {
+                Ok(point) => point,
+                Err(_) => break,
+            };
             grid.set(&point, ValueType::one());
             backboneseeds.push(point);
         }
@@ -75,16 +279,26 @@ impl<ScaleType,ValueType> PipeGrid<ScaleType,ValueType> for Grid<ScaleType,Value
             if let Some(closest) = closest {
                 let point2 = backboneseeds[closest];
                 grid.randompathto(&mut rng, point, &point2, ValueType::from_u8(2).unwrap());
+                observer(&GenerationEvent::BackbonePath(*point, point2), &grid);
             }
         }
 
         //Add regular nodes (multiple iterations of a specific amount of seeds)
         for (iternr, regularseedgoal) in properties.regularseeds.iter().enumerate() {
+            let mut rng: R = seed.split("regularseeds").rng(&iternr.to_string());
             let mut regularseeds = 0;
             let height: ValueType = ValueType::from_usize(iternr).expect("Conversion error") + ValueType::from_u8(3).unwrap();
             while regularseeds < *regularseedgoal {
-                let point = Point::random(&mut rng, &grid.rectangle());
-                if grid[&point] == ValueType::zero() {
+                let point = match Point::random(&mut rng, &grid.rectangle()) {
+                    Ok(point) => point,
+                    Err(_) => break,
+                };
+                if grid[&point] != ValueType::zero() {
+                    //small maps may fill up before all seeds are placed
+                    if grid.iter().all(|(_, v)| *v != ValueType::zero()) {
+                        break;
+                    }
+                } else {
                     regularseeds += 1;
                     grid.set(&point,height);
                     //find the closest backbone
@@ -103,106 +317,392 @@ impl<ScaleType,ValueType> PipeGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                     //draw a random path to the closest backbone
                     if let Some(point2) = closest {
                         grid.randompathto(&mut rng, &point, &point2, height+ValueType::one());
+                        observer(&GenerationEvent::Branch { seed: point, target: point2, tier: iternr + 1 }, &grid);
                     }
                 }
             }
         }
 
         if properties.interconnect {
+            let mut rng: R = seed.rng("interconnect");
             //prune dead ends by creating more interconnections
-            let mut deadends: Vec<Point<ScaleType>> = Vec::new();
-            let mut processed: Vec<Point<ScaleType>> = Vec::new();
-            //find all dead ends
-            for (point,value) in grid.iter() {
-               if *value > ValueType::from_u8(2).unwrap() && grid.countneighbours(&point) == 1 {
-                   deadends.push(point);
-               }
+            let deadends = grid.deadends();
+            let mut isdeadend: Grid<ScaleType,bool> = Grid::new(grid.width(), grid.height());
+            for point in deadends.iter() {
+                isdeadend.set(point, true);
             }
+            //dead ends that have already been connected to by another dead end
+            let mut processed: Grid<ScaleType,bool> = Grid::new(grid.width(), grid.height());
+            let mut loops = 0;
 
             for point in deadends.iter() {
-              if !processed.contains(point) {
-                //we find the closest other dead end (or former dead end)
-                let mut mindistance: Option<f64> = None;
-                let mut closest: Option<Point<ScaleType>> = None;
-                for point2 in deadends.iter() {
-                    if point != point2 {
-                        let distance: f64 = point.distance(&point2);
-                        if mindistance.is_none() || distance < mindistance.unwrap() {
-                            mindistance = Some(distance);
-                            closest = Some(point2.clone());
-                        }
+              if properties.maxloops.is_some() && loops >= properties.maxloops.unwrap() {
+                  break;
+              }
+              if !processed[point] {
+                let closest: Option<Point<ScaleType>> = match properties.interconnect_target {
+                    InterconnectTarget::DeadEnds => {
+                        //we find the closest other dead end (or former dead end)
+                        isdeadend.nearest(point, |_, isdeadend| *isdeadend)
+                    },
+                    InterconnectTarget::Pipes => {
+                        //we find the closest pipe that is not on the branch this dead end is on
+                        let ownbranch: Vec<Point<ScaleType>> = match grid.deadendbranch(point) {
+                            Some((mut branch, junction)) => {
+                                branch.push(junction);
+                                branch
+                            },
+                            None => vec!(*point),
+                        };
+                        grid.nearest(point, |point2, v| *v != ValueType::zero() && !ownbranch.contains(point2))
                     }
-                }
-                //draw a random path to the closest (former) dead end
+                };
+                //draw a random path to the closest target
                 if let Some(closest) = closest {
-                    grid.randompathto(&mut rng, point, &closest, ValueType::from_u8(99).unwrap());
-                    processed.push(closest);
+                    grid.randompathto(&mut rng, point, &closest, ValueType::from_u8(INTERCONNECT).unwrap());
+                    observer(&GenerationEvent::Interconnection(*point, closest), &grid);
+                    processed.set(&closest, true);
+                    loops += 1;
                 }
               }
             }
         }
+
+        if properties.prune > 0 {
+            let pruned = grid.prune(properties.prune);
+            observer(&GenerationEvent::Pruned(pruned), &grid);
+        }
+
+        if properties.symmetry != Symmetry::None {
+            //connect the network to the centre so it connects to its symmetric copies
+            let anchors = properties.symmetry.anchors(width, height);
+            if let Some(anchor) = anchors.first() {
+                if !grid.is_set(anchor) {
+                    if let Some(closest) = grid.nearest(anchor, |_, v| *v != ValueType::zero()) {
+                        let value = grid[&closest];
+                        grid.randompathto(&mut seed.rng::<R>("symmetry"), &closest, anchor, value);
+                    }
+                }
+            }
+            for anchor in anchors.iter() {
+                if !grid.is_set(anchor) {
+                    grid.set(anchor, ValueType::from_u8(2).unwrap());
+                }
+            }
+            grid = grid.symmetric(width, height, properties.symmetry);
+            observer(&GenerationEvent::Symmetry(properties.symmetry), &grid);
+        }
         grid
     }
 
     fn render(&self,renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell> {
+        self.render_with_connections(&PipeGrid::connections(self), renderstyle)
+    }
+
+    ///Renders the network with the given connections between cells (see `connections()`), rather
+    ///than connecting all neighbouring pipe cells, e.g. for maps read with `TextGrid::from_pipetext`
+    fn render_with_connections(&self, connections: &Grid<ScaleType,u8>, renderstyle: PipeRenderStyle) -> Grid<ScaleType,RenderedTextCell> {
         let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
-        for (i, point) in self.rectangle().iter().enumerate() {
-            renderedgrid.set(&point,  PipeGrid::rendercell(self, &point, renderstyle) );
+        for point in self.rectangle().iter() {
+            renderedgrid.set(&point, pipecell(self[&point], connections[&point], renderstyle));
         }
         renderedgrid
     }
 
     fn rendercell(&self, point: &Point<ScaleType>, renderstyle: PipeRenderStyle) -> RenderedTextCell {
-        let v = self[point];
-        let chr: char = if v == ValueType::zero() {
-            ' '
-        } else {
-           let (hasnorth, haseast, hassouth, haswest) = self.hasneighbours(point);
-           let isbackbone = v <= ValueType::from_u8(2).unwrap();
-           match renderstyle {
-               PipeRenderStyle::Thick => '█',
-               PipeRenderStyle::Thin => {
-                   match (hasnorth, haseast, hassouth, haswest, isbackbone) {
-                       (true,true,true,true, false) => '┼',
-                       (true,true,true,true, true) => '╋',
-                       (true,true,true,false, false) => '├',
-                       (true,true,true,false, true) => '┣',
-                       (false,true,true,true, false) => '┬',
-                       (false,true,true,true, true) => '┳',
-                       (true,false,true,true, false) => '┤',
-                       (true,false,true,true, true) => '┫',
-                       (true,true,false,true, false) => '┴',
-                       (true,true,false,true, true) => '┻',
-                       (true,true,false,false, false) => '└',
-                       (true,true,false,false, true) => '┗',
-                       (true,false,true,false, false) => '│',
-                       (true,false,true,false, true) => '┃',
-                       (true,false,false,true, false) => '┘',
-                       (true,false,false,true, true) => '┛',
-                       (false,true,true,false, false) => '┌',
-                       (false,true,true,false, true) => '┏',
-                       (false,true,false,true, false) => '─',
-                       (false,true,false,true, true) => '━',
-                       (false,false,true,true, false) => '┐',
-                       (false,false,true,true, true) => '┓',
-                       (true,false,false,false, false) => '╵',
-                       (true,false,false,false, true) => '╹',
-                       (false,true,false,false, false) => '╶',
-                       (false,true,false,false, true) => '╺',
-                       (false,false,true,false, false) => '╷',
-                       (false,false,true,false, true) => '╻',
-                       (false,false,false,true, false) => '╴',
-                       (false,false,false,true, true) => '╸',
-                       _ => '?',
-                   }
+        let (hasnorth, haseast, hassouth, haswest) = self.hasneighbours(point);
+        pipecell(self[point], connectionmask(hasnorth, haseast, hassouth, haswest), renderstyle)
+    }
+
+    ///Returns the connections of every pipe cell as a bitmask of `textmap::NORTH`, `EAST`,
+    ///`SOUTH` and `WEST`. In generated networks all neighbouring pipe cells are connected.
+    fn connections(&self) -> Grid<ScaleType,u8> {
+        let mut connections: Grid<ScaleType,u8> = Grid::new(self.width(), self.height());
+        for (point, v) in self.iter() {
+            if *v != ValueType::zero() {
+                let (hasnorth, haseast, hassouth, haswest) = self.hasneighbours(&point);
+                connections.set(&point, connectionmask(hasnorth, haseast, hassouth, haswest));
+            }
+        }
+        connections
+    }
+
+    ///Returns all dead ends, i.e. non-backbone pipe cells with only a single neighbour
+    fn deadends(&self) -> Vec<Point<ScaleType>> {
+        self.deadends_with_connections(&PipeGrid::connections(self))
+    }
+
+    ///Returns all dead ends given the connections between cells (see `connections()`), i.e.
+    ///non-backbone pipe cells with only a single connection
+    fn deadends_with_connections(&self, connections: &Grid<ScaleType,u8>) -> Vec<Point<ScaleType>> {
+        let mut deadends: Vec<Point<ScaleType>> = Vec::new();
+        for (point,value) in self.iter() {
+           if *value > ValueType::from_u8(2).unwrap() && connections[&point].count_ones() == 1 {
+               deadends.push(point);
+           }
+        }
+        deadends
+    }
+
+    ///Follows the branch from a dead end back to where it joins the rest of the network. Returns
+    ///the cells of the branch (starting with the dead end) and the junction it connects to, or None if
+    ///the branch is not connected to anything else.
+    fn deadendbranch(&self, point: &Point<ScaleType>) -> Option<(Vec<Point<ScaleType>>, Point<ScaleType>)> {
+        let mut branch: Vec<Point<ScaleType>> = vec!(*point);
+        let mut previous = *point;
+        let mut current = *point;
+        loop {
+            let next = self.getneighbours(&current).into_iter().find(|neighbour| *neighbour != previous && self.is_set(neighbour))?;
+            let count = self.countneighbours(&next);
+            if count == 1 {
+                //we reached another dead end, this is an isolated segment
+                return None;
+            } else if count > 2 || self[&next] <= ValueType::from_u8(2).unwrap() {
+                //we reached a junction or the backbone
+                return Some((branch, next));
+            }
+            branch.push(next);
+            previous = current;
+            current = next;
+        }
+    }
+
+    ///Removes all dead-end branches shorter than the specified number of cells, returns the
+    ///number of branches removed
+    fn prune(&mut self, minlength: usize) -> usize {
+        let mut pruned = 0;
+        for point in self.deadends() {
+            if self.is_set(&point) {
+                if let Some((branch, _)) = self.deadendbranch(&point) {
+                    if branch.len() < minlength {
+                        for point in branch.iter() {
+                            self.set(point, ValueType::zero());
+                        }
+                        pruned += 1;
+                    }
                 }
             }
-        };
-        RenderedTextCell {
-            background_colour: None,
-            foreground_colour: None,
-            text: Some(chr.to_string())
         }
+        pruned
+    }
+
+    ///Returns the tier of a pipe cell: 0 for the backbone, 1 for the seeds of the first iteration
+    ///of regular seeds, 2 for the paths grown from them and the seeds of the second iteration, etc.
+    ///Interconnections are in the highest tier.
+    fn tier(&self, point: &Point<ScaleType>) -> usize {
+        let v = self[point].to_usize().unwrap_or(0);
+        v.saturating_sub(2)
+    }
+
+    ///Is the cell one of the seeds the network was grown from? Seeds are not stored as such: a
+    ///seed has no neighbour with the same value (its path has the next value), unless it is the
+    ///single cell of a path grown from a seed next to it. Seeds placed right next to a path with
+    ///the same value can not be told apart from that path.
+    fn isseed(&self, point: &Point<ScaleType>) -> bool {
+        let value = self[point];
+        if value == ValueType::zero() || value == ValueType::from_u8(2).unwrap() || value >= ValueType::from_u8(INTERCONNECT).unwrap() {
+            return false;
+        }
+        self.getneighbours(point).iter().all(|neighbour| {
+            self[neighbour] != value && !(self[neighbour] + ValueType::one() == value && self.isseed(neighbour))
+        })
     }
 
+    ///Renders the network as vector graphics, with every connection between two neighbouring cells
+    ///as a line segment. The stroke gets thinner with every tier, a connection between
+    ///two tiers takes the thinner stroke.
+    fn svg(&self, cellsize: f64) -> Svg {
+        let mut svg = Svg::new(self.width_as_usize() as f64 * cellsize, self.height_as_usize() as f64 * cellsize);
+        svg.rect(0.0, 0.0, svg.width, svg.height, DEFAULT_BACKGROUND);
+        let centre = |point: &Point<ScaleType>| ((point.xs() as f64 + 0.5) * cellsize, (point.ys() as f64 + 0.5) * cellsize);
+        let strokewidth = |tier: usize| cellsize * (0.5 - 0.1 * tier as f64).max(0.15);
+        let mut segments: BTreeMap<usize,Vec<Segment>> = BTreeMap::new();
+        for (point, v) in self.iter() {
+            if *v == ValueType::zero() {
+                continue;
+            }
+            let tier = PipeGrid::tier(self, &point);
+            if self.countneighbours(&point) == 0 {
+                let (x, y) = centre(&point);
+                svg.circle(x, y, strokewidth(tier) / 2.0, DEFAULT_FOREGROUND);
+            }
+            //only look east and south so every connection is drawn once
+            for direction in [Direction::East, Direction::South].iter() {
+                if let Some(neighbour) = self.getneighbour(&point, *direction) {
+                    if self[&neighbour] != ValueType::zero() {
+                        let segmenttier = tier.max(PipeGrid::tier(self, &neighbour));
+                        segments.entry(segmenttier).or_default().push((centre(&point), centre(&neighbour)));
+                    }
+                }
+            }
+        }
+        for (tier, segments) in segments.iter() {
+            svg.lines(segments, DEFAULT_FOREGROUND, strokewidth(*tier));
+        }
+        svg
+    }
+
+    ///Exports the network as a Tiled map. The tileset holds all junctions, each of them both for
+    ///regular pipes and for the backbone, the seeds are included as point objects.
+    fn tiled(&self, tilesize: usize) -> TiledMap {
+        let mut map = TiledMap::new(self.width_as_usize(), self.height_as_usize(), tilesize);
+        //tile IDs follow from the connections: north = 1, east = 2, south = 4, west = 8, plus 16 for the backbone
+        for i in 0..32 {
+            let arms = [i & 1 != 0, i & 2 != 0, i & 4 != 0, i & 8 != 0];
+            let isbackbone = i & 16 != 0;
+            let names: Vec<&str> = ["north","east","south","west"].iter().zip(arms.iter()).filter(|(_, arm)| **arm).map(|(name, _)| *name).collect();
+            let kind = format!("{} {}", if isbackbone { "backbone" } else { "pipe" }, if names.is_empty() { "isolated".to_string() } else { names.join("-") });
+            map.addtile(&kind, RenderedTextCell {
+                background_colour: None,
+                foreground_colour: None,
+                text: Some(pipechar(arms[0], arms[1], arms[2], arms[3], isbackbone).to_string()),
+            });
+        }
+        let mut seeds = Vec::new();
+        for (point, v) in self.iter() {
+            if *v == ValueType::zero() {
+                continue;
+            }
+            let (hasnorth, haseast, hassouth, haswest) = self.hasneighbours(&point);
+            let isbackbone = *v <= ValueType::from_u8(2).unwrap();
+            let tile = hasnorth as u32 + 2 * haseast as u32 + 4 * hassouth as u32 + 8 * haswest as u32 + if isbackbone { 16 } else { 0 };
+            map.set(&point, tile + 1);
+            if self.isseed(&point) {
+                seeds.push(TiledObject {
+                    name: format!("seed {}", seeds.len() + 1),
+                    kind: if isbackbone { "backbone seed".to_string() } else { format!("tier {} seed", PipeGrid::tier(self, &point)) },
+                    x: point.xs(),
+                    y: point.ys(),
+                    width: 0,
+                    height: 0,
+                });
+            }
+        }
+        map.objectgroups.push(ObjectGroup { name: "seeds".to_string(), objects: seeds });
+        map
+    }
+}
+
+impl FromStr for InterconnectTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deadends" => Ok(InterconnectTarget::DeadEnds),
+            "pipes" => Ok(InterconnectTarget::Pipes),
+            _ => Err(format!("No such interconnect target: {}", s)),
+        }
+    }
+}
+
+///Returns a rendered pipe cell for its value and connections (a bitmask, see `PipeGrid::connections`)
+fn pipecell<ValueType>(value: ValueType, connections: u8, renderstyle: PipeRenderStyle) -> RenderedTextCell where
+    ValueType: Num + FromPrimitive + PartialOrd {
+    let chr: char = if value == ValueType::zero() {
+        ' '
+    } else {
+       let (hasnorth, haseast, hassouth, haswest) = connectionarms(connections);
+       let isbackbone = value <= ValueType::from_u8(2).unwrap();
+       match renderstyle {
+           PipeRenderStyle::Thick => '█',
+           PipeRenderStyle::Thin => pipechar(hasnorth, haseast, hassouth, haswest, isbackbone),
+       }
+    };
+    RenderedTextCell {
+        background_colour: None,
+        foreground_colour: None,
+        text: Some(chr.to_string())
+    }
+}
+
+///Returns the render style for its name, the thin style is the default
+pub fn renderstyle(style: &str) -> PipeRenderStyle {
+    match style {
+        "thick" => PipeRenderStyle::Thick,
+        _ => PipeRenderStyle::Thin
+    }
+}
+
+///Generates pipe networks through `PipeGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct PipeGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for PipeGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = PipeGridProperties;
+
+    fn name(&self) -> &'static str {
+        "pipes"
+    }
+
+    fn description(&self) -> &'static str {
+        "An interconnected network of pipes, with a thicker backbone and regular pipes branching off it"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("backboneseeds", "Number of seeds of the backbone", Some("20")),
+            ParameterInfo::new("regularseeds", "Number of regular seeds per iteration, comma separated", Some("40,40,60")),
+            ParameterInfo::new("interconnect", "Interconnect dead ends, resulting in fewer dead ends (true or false)", Some("false")),
+            ParameterInfo::new("interconnecttarget", "What to interconnect dead ends with: deadends or pipes", Some("deadends")),
+            ParameterInfo::new("maxloops", "Maximum number of interconnections to add", None),
+            ParameterInfo::new("prune", "Remove dead-end branches shorter than this number of cells (0 = disabled)", Some("0")),
+            ParameterInfo::new("symmetry", "Symmetry to enforce: none, horizontal, vertical, rotate2, rotate4", Some("none")),
+        )
+    }
+
+    fn styles(&self) -> &'static [&'static str] {
+        &["thin", "thick"]
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<PipeGridProperties, Error> {
+        let regularseeds: String = parameter(parameters, "regularseeds")?;
+        Ok(PipeGridProperties {
+            backboneseeds: parameter(parameters, "backboneseeds")?,
+            regularseeds: regularseeds.split_terminator(',').map(|x| x.parse::<u16>().map_err(|_| Error::InvalidParameter(format!("Invalid value for parameter regularseeds: {}", regularseeds)))).collect::<Result<Vec<u16>,Error>>()?,
+            interconnect: parameter(parameters, "interconnect")?,
+            interconnect_target: parameter(parameters, "interconnecttarget")?,
+            maxloops: optionalparameter(parameters, "maxloops")?,
+            prune: parameter(parameters, "prune")?,
+            symmetry: parameter(parameters, "symmetry")?,
+        })
+    }
+
+    fn validate(&self, width: ScaleType, height: ScaleType, properties: &PipeGridProperties) -> Result<(), Error> {
+        properties.validate(width.to_usize().ok_or(Error::OutOfBounds)?, height.to_usize().ok_or(Error::OutOfBounds)?)
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &PipeGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as PipeGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone()))
+    }
+
+    fn generate_with_events(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &PipeGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as PipeGrid<ScaleType,ValueType>>::generate_with_events(width, height, seed, properties.clone(), observer))
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, style: &str, _properties: &PipeGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        PipeGrid::render(grid, renderstyle(style))
+    }
+
+    fn svg(&self, grid: &Grid<ScaleType,ValueType>, cellsize: f64, _style: &str) -> Option<Svg> {
+        Some(PipeGrid::svg(grid, cellsize))
+    }
+
+    fn tiled(&self, grid: &Grid<ScaleType,ValueType>, tilesize: usize, _style: &str) -> Option<TiledMap> {
+        Some(PipeGrid::tiled(grid, tilesize))
+    }
+
+    fn describe(&self, grid: &Grid<ScaleType,ValueType>, point: &Point<ScaleType>) -> String {
+        let value = grid[point].to_usize().unwrap();
+        match value {
+            0 => "empty".to_string(),
+            _ if value == INTERCONNECT as usize => format!("interconnection (value {})", value),
+            1 | 2 if grid.isseed(point) => format!("backbone seed (value {})", value),
+            1 | 2 => format!("backbone (value {})", value),
+            _ if grid.isseed(point) => format!("seed, tier {} (value {})", grid.tier(point), value),
+            _ => format!("tier {} (value {})", grid.tier(point), value),
+        }
+    }
 }
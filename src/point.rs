@@ -1,12 +1,12 @@
-use rand::{SeedableRng,Rng};
-use rand_pcg::Pcg32;
-use num::{Integer,Num,FromPrimitive,ToPrimitive,range};
+use rand::Rng;
+use num::{Integer,FromPrimitive,ToPrimitive};
 use std::ops::{Add,AddAssign};
-use std::cmp::{min,PartialEq,Eq,Ord,Ordering};
+use std::cmp::{PartialEq,Eq,Ord,Ordering};
 use std::fmt;
 
 use crate::common::{Distance,Direction};
 use crate::rectangle::Rectangle;
+use crate::error::{Error,Result};
 
 ///A Point in an X,Y plane
 #[derive(Debug,Clone,Copy,PartialEq,Eq)]
@@ -20,16 +20,34 @@ impl<ScaleType> Point<ScaleType> where
         Point(x,y)
     }
 
-    //conversion
+    //conversion, these panic if the coordinates do not fit in the scale type, see `try_new_usize()`
+    //and `try_new64()` for fallible versions
     pub fn new_usize(x: usize, y: usize) -> Point<ScaleType> { Point(ScaleType::from_usize(x).expect("Out of bounds"), ScaleType::from_usize(y).expect("Out of bounds")) }
     pub fn new64(x: u64, y: u64) -> Point<ScaleType> { Point(ScaleType::from_u64(x).expect("Out of bounds"), ScaleType::from_u64(y).expect("Out of bounds")) }
     pub fn new32(x: u32, y: u32) -> Point<ScaleType> { Point(ScaleType::from_u32(x).expect("Out of bounds"), ScaleType::from_u32(y).expect("Out of bounds")) }
     pub fn new16(x: u16, y: u16) -> Point<ScaleType> { Point(ScaleType::from_u16(x).expect("Out of bounds"), ScaleType::from_u16(y).expect("Out of bounds")) }
     pub fn new8(x: u8, y: u8) -> Point<ScaleType> { Point(ScaleType::from_u8(x).expect("Out of bounds"), ScaleType::from_u8(y).expect("Out of bounds")) }
 
-    //Generate a random point within the specified rectangular bound
-    pub fn random(rng: &mut Pcg32, bounds: &Rectangle<ScaleType>) -> Point<ScaleType> {
-        Point::new64(
+    pub fn try_new_usize(x: usize, y: usize) -> Result<Point<ScaleType>> {
+        match (ScaleType::from_usize(x), ScaleType::from_usize(y)) {
+            (Some(x), Some(y)) => Ok(Point(x,y)),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+
+    pub fn try_new64(x: u64, y: u64) -> Result<Point<ScaleType>> {
+        match (ScaleType::from_u64(x), ScaleType::from_u64(y)) {
+            (Some(x), Some(y)) => Ok(Point(x,y)),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+
+    ///Generate a random point within the specified rectangular bound, fails if the bound is empty
+    pub fn random<R: Rng + ?Sized>(rng: &mut R, bounds: &Rectangle<ScaleType>) -> Result<Point<ScaleType>> {
+        if bounds.topleft.x() > bounds.bottomright.x() || bounds.topleft.y() > bounds.bottomright.y() {
+            return Err(Error::EmptyRange);
+        }
+        Point::try_new64(
                 rng.gen_range(bounds.topleft.xs(),bounds.bottomright.xs() + 1) as u64,
                 rng.gen_range(bounds.topleft.ys(),bounds.bottomright.ys() + 1) as u64
         )
@@ -51,7 +69,7 @@ impl<ScaleType> Point<ScaleType> where
         Rectangle::new_dims(self.x(), self.y(), ScaleType::one(), ScaleType::one())
     }
 
-    //conversion
+    //conversion, these panic for coordinates that do not fit (e.g. negative ones for `xs()`)
     pub fn xs(&self) -> usize { self.0.to_usize().expect("Out of bounds") }
     pub fn ys(&self) -> usize { self.1.to_usize().expect("Out of bounds") }
     pub fn x64(&self) -> u64 { self.0.to_u64().expect("Out of bounds") }
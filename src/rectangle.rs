@@ -1,14 +1,11 @@
-use rand::{SeedableRng,Rng};
-use rand_pcg::Pcg32;
-use clap::{App,Arg};
-use num::{Integer,Num,FromPrimitive,ToPrimitive,range};
-use std::ops::Index;
+use rand::Rng;
+use num::{Integer,FromPrimitive,ToPrimitive};
 use std::cmp::{min,PartialEq,Eq};
-use std::fmt;
 use std::iter::Iterator;
 
-use crate::common::{Distance,Direction,Volume,fmin};
+use crate::common::{Distance,Volume,fmin};
 use crate::point::Point;
+use crate::error::{Error,Result};
 
 
 #[derive(Debug,Clone,Copy,PartialEq,Eq)]
@@ -29,8 +26,8 @@ impl<ScaleType> Rectangle<ScaleType> where
 
     pub fn new(topleft: &Point<ScaleType>, bottomright: &Point<ScaleType>) -> Rectangle<ScaleType> {
         Rectangle {
-            topleft: topleft.clone(),
-            bottomright: bottomright.clone()
+            topleft: *topleft,
+            bottomright: *bottomright
         }
     }
 
@@ -59,8 +56,8 @@ impl<ScaleType> Rectangle<ScaleType> where
     }
 
     ///Return a random point in the rectangle
-    pub fn randompoint(&self, mut rng: &mut Pcg32) -> Point<ScaleType> {
-        return Point::random(&mut rng, &self);
+    pub fn randompoint<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Point<ScaleType>> {
+        Point::random(rng, self)
     }
 
     pub fn new_dims(x: ScaleType, y: ScaleType, width: ScaleType, height: ScaleType) -> Rectangle<ScaleType> {
@@ -70,35 +67,49 @@ impl<ScaleType> Rectangle<ScaleType> where
         }
     }
     //
-    ///Generate a random rectangle within the specified rectangular bound
-    pub fn random(rng: &mut Pcg32, bounds: &Rectangle<ScaleType>, minwidth: Option<ScaleType>, maxwidth: Option<ScaleType>, minheight: Option<ScaleType>, maxheight: Option<ScaleType>) -> Rectangle<ScaleType> {
-        let minwidth = minwidth.unwrap_or(ScaleType::one()).to_usize().unwrap();
-        let maxwidth = maxwidth.unwrap_or(bounds.width()).to_usize().unwrap();
-        let minheight = minheight.unwrap_or(ScaleType::one()).to_usize().unwrap();
-        let maxheight = maxheight.unwrap_or(bounds.height()).to_usize().unwrap();
-        let topleft = Point::new64(
+    ///Generate a random rectangle within the specified rectangular bound. Maximum sizes smaller
+    ///than the minimum sizes are raised to the minimum. Fails if the bound is too small for a
+    ///rectangle of the minimum size.
+    pub fn random<R: Rng + ?Sized>(rng: &mut R, bounds: &Rectangle<ScaleType>, minwidth: Option<ScaleType>, maxwidth: Option<ScaleType>, minheight: Option<ScaleType>, maxheight: Option<ScaleType>) -> Result<Rectangle<ScaleType>> {
+        let minwidth = minwidth.unwrap_or(ScaleType::one()).to_usize().ok_or(Error::OutOfBounds)?;
+        let maxwidth = maxwidth.unwrap_or(bounds.width()).to_usize().ok_or(Error::OutOfBounds)?;
+        let minheight = minheight.unwrap_or(ScaleType::one()).to_usize().ok_or(Error::OutOfBounds)?;
+        let maxheight = maxheight.unwrap_or(bounds.height()).to_usize().ok_or(Error::OutOfBounds)?;
+        if bounds.bottomright.xs() < bounds.topleft.xs() + minwidth || bounds.bottomright.ys() < bounds.topleft.ys() + minheight {
+            return Err(Error::EmptyRange);
+        }
+        let topleft: Point<ScaleType> = Point::try_new64(
                 rng.gen_range(bounds.topleft.xs(), bounds.bottomright.xs() + 1 - minwidth) as u64,
                 rng.gen_range(bounds.topleft.ys(), bounds.bottomright.ys() + 1 - minheight) as u64
-        );
-        let bottomright = Point::new64(
-                rng.gen_range(topleft.xs() + minwidth, min(topleft.xs() + maxwidth, bounds.bottomright.xs() + 1)) as u64,
-                rng.gen_range(topleft.ys() + minheight, min(topleft.ys() + maxheight,  bounds.bottomright.ys() + 1) )as u64
-        );
-        Rectangle {
-            topleft: topleft,
-            bottomright: bottomright,
-        }
+        )?;
+        let bottomright = Point::try_new64(
+                randomupto(rng, topleft.xs() + minwidth, min(topleft.xs() + maxwidth, bounds.bottomright.xs() + 1)) as u64,
+                randomupto(rng, topleft.ys() + minheight, min(topleft.ys() + maxheight,  bounds.bottomright.ys() + 1)) as u64
+        )?;
+        Ok(Rectangle {
+            topleft,
+            bottomright,
+        })
     }
 
     ///Iterate over all points in the rectangle
     pub fn iter(&self) -> RectIterator<ScaleType> {
         RectIterator {
-            rectangle: self.clone(),
+            rectangle: *self,
             current: None,
         }
     }
 }
 
+///A random number in the range low..high, or low if the range is empty
+fn randomupto<R: Rng + ?Sized>(rng: &mut R, low: usize, high: usize) -> usize {
+    if high > low {
+        rng.gen_range(low, high)
+    } else {
+        low
+    }
+}
+
 impl<ScaleType> Volume<ScaleType> for Rectangle<ScaleType> where
     ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
 
@@ -163,7 +174,7 @@ impl<ScaleType> Iterator for RectIterator<ScaleType> where
                 self.current = Some(current);
             }
         } else {
-            self.current = Some(self.rectangle.topleft.clone());
+            self.current = Some(self.rectangle.topleft);
         };
         self.current
     }
@@ -1,15 +1,99 @@
 use rand::{SeedableRng,Rng};
 use rand_pcg::Pcg32;
-use std::cmp::{min,max,PartialEq,Eq};
+use std::cmp::{min,max,PartialEq};
 use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,range,CheckedAdd,CheckedSub};
 
-use crate::common::{Distance,Direction,Volume};
+use crate::common::{Direction,Distance,Volume};
 use crate::point::Point;
 use crate::rectangle::Rectangle;
 use crate::grid::{Grid,GenericGrid,NumericGrid,RenderedTextCell};
+use crate::symmetry::Symmetry;
+use crate::seed::Seed;
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter,checkdimensions,describesize};
+use crate::events::{GenerationEvent,Observer};
+use crate::svg::Svg;
+use crate::tiled::{TiledMap,TiledObject,ObjectGroup};
 
+#[derive(Debug,Clone,Default)]
 pub struct RoomGridProperties {
     pub rooms: usize,
+
+    ///symmetry to enforce on the map
+    pub symmetry: Symmetry,
+}
+
+///Rooms are at least this wide and high, and at most a quarter of the width and height of the map
+pub const MINROOMSIZE: usize = 3;
+
+impl RoomGridProperties {
+    ///Starts building properties for a map of the given size, with the defaults of the `rooms`
+    ///generator
+    pub fn builder(width: usize, height: usize) -> RoomGridPropertiesBuilder {
+        RoomGridPropertiesBuilder {
+            width,
+            height,
+            properties: RoomGridProperties {
+                rooms: 6,
+                symmetry: Symmetry::None,
+            },
+        }
+    }
+
+    ///Checks that the properties can be used for a map of the given size: a quarter of the map
+    ///must fit the smallest room, and the rooms, which do not overlap, must fit the map
+    pub fn validate(&self, width: usize, height: usize) -> Result<(), Error> {
+        checkdimensions(width, height)?;
+        if width / 4 < MINROOMSIZE || height / 4 < MINROOMSIZE {
+            return Err(Error::InvalidParameter(format!("Rooms are at least {}x{} and at most a quarter of the map in either direction, so the map must be at least {}x{}, got {}x{}",
+                       MINROOMSIZE, MINROOMSIZE, 4 * MINROOMSIZE, 4 * MINROOMSIZE, width, height)));
+        }
+        let (sectorwidth, sectorheight) = self.symmetry.sector(width, height);
+        let maxrooms = (sectorwidth / (MINROOMSIZE + 1)) * (sectorheight / (MINROOMSIZE + 1));
+        if self.rooms > maxrooms {
+            return Err(Error::InvalidParameter(format!("{} rooms do not fit in a {}, there is room for at most {}",
+                       self.rooms, describesize(width, height, self.symmetry), maxrooms)));
+        }
+        Ok(())
+    }
+}
+
+///Builds `RoomGridProperties` that are checked against the size of the map, see
+///`RoomGridProperties::builder()`
+#[derive(Debug,Clone)]
+pub struct RoomGridPropertiesBuilder {
+    width: usize,
+    height: usize,
+    properties: RoomGridProperties,
+}
+
+impl RoomGridPropertiesBuilder {
+    pub fn rooms(mut self, rooms: usize) -> Self {
+        self.properties.rooms = rooms;
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.properties.symmetry = symmetry;
+        self
+    }
+
+    ///Returns the properties, or a description of why they do not fit the map
+    pub fn build(self) -> Result<RoomGridProperties, Error> {
+        self.properties.validate(self.width, self.height)?;
+        Ok(self.properties)
+    }
+}
+
+///The value of corridor cells, see [`RoomGrid::corridor`]
+pub fn corridorvalue<ValueType: Bounded>() -> ValueType {
+    ValueType::max_value()
+}
+
+///The value of the cells of a room, room numbers saturate just below the corridor value
+fn roomvalue<ValueType>(number: usize) -> ValueType where
+    ValueType: Num + FromPrimitive + PartialOrd + Bounded + Copy {
+    ValueType::from_usize(number).filter(|v| *v < corridorvalue()).unwrap_or(corridorvalue::<ValueType>() - ValueType::one())
 }
 
 pub trait RoomGrid<ScaleType, ValueType> where
@@ -17,26 +101,54 @@ pub trait RoomGrid<ScaleType, ValueType> where
     ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
 
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties) -> Grid<ScaleType,ValueType>;
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType>;
     fn render(&self) -> Grid<ScaleType,RenderedTextCell>;
     fn rendercell(&self, point: &Point<ScaleType>) -> RenderedTextCell;
+    fn corridor(&self, point: &Point<ScaleType>) -> bool;
+    fn rooms(&self) -> Vec<(ValueType,Rectangle<ScaleType>)>;
+    fn svg(&self, cellsize: f64) -> Svg;
+    fn tiled(&self, tilesize: usize) -> TiledMap;
 }
 
 impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
     ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
     ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
 
+    ///Generates rooms connected by corridors. Cells of a room hold the room number (starting at one,
+    ///copies made by symmetry are numbered after the original rooms), corridors hold the maximum
+    ///value and walls are zero.
     fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties) -> Grid<ScaleType,ValueType> {
-        let mut rng = Pcg32::seed_from_u64(seed);
-        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width,height);
+        Self::generate_with_events(width, height, seed, properties, &mut |_, _| {})
+    }
+
+    ///Generates rooms like `generate()`, reporting every room and corridor to the observer
+    fn generate_with_events(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties, observer)
+    }
+
+    ///Generates rooms like `generate_with_events()`, with random number generators of the given type
+    ///(for the rooms and the corridors, seeded from `seed`)
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: RoomGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Grid<ScaleType,ValueType> {
+        //rooms and corridors have their own random number generators, so they do not influence each other
+        let seed = Seed::from(seed);
+        let mut rng: R = seed.rng("rooms");
+        let mut corridorrng: R = seed.rng("corridors");
+        //with symmetry we only generate a single sector
+        let (sectorwidth, sectorheight) = properties.symmetry.sector(width, height);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(sectorwidth,sectorheight);
         let mut rooms: Vec<Rectangle<ScaleType>> = Vec::new(); //left,top,width,height
         let mut tries = 0;
         while rooms.len() < properties.rooms && tries < 100 { //we give adding rooms when we fail after 100 tries
-            let room: Rectangle<ScaleType> = Rectangle::random(&mut rng, &grid.rectangle(),
-                               Some(ScaleType::from_u8(3).expect("conversion error")),  //minwidth
-                               Some(ScaleType::from_usize(grid.width_as_usize() / 4).expect("conversion error")), //maxwidth
-                               Some(ScaleType::from_u8(3).expect("conversion error")),  //minheight
-                               Some(ScaleType::from_usize(grid.height_as_usize() / 4).expect("conversion error")), //maxheight
-            );
+            let room: Rectangle<ScaleType> = match Rectangle::random(&mut rng, &grid.rectangle(),
+                               Some(ScaleType::from_usize(MINROOMSIZE).expect("conversion error")),  //minwidth
+                               Some(width / ScaleType::from_u8(4).unwrap()), //maxwidth
+                               Some(ScaleType::from_usize(MINROOMSIZE).expect("conversion error")),  //minheight
+                               Some(height / ScaleType::from_u8(4).unwrap()), //maxheight
+            ) {
+                Ok(room) => room,
+                Err(_) => break, //the map is too small for any room
+            };
 
             //the room may not overlap with others
             let mut overlaps = false;
@@ -51,10 +163,12 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                 continue;
             }
 
+            let roomvalue: ValueType = roomvalue(rooms.len() + 1);
             for point in room.iter() {
-                grid.inc(&point, ValueType::one());
+                grid.set(&point, roomvalue);
             }
             tries = 0;
+            observer(&GenerationEvent::Room(room), &grid);
 
             //we add the current room AFTER the following block:
             //
@@ -63,7 +177,7 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                 let mut mindistance: Option<f64> = None;
                 let mut closest: Option<usize> = None;
                 for (i, room2) in rooms.iter().enumerate() {
-                    let distance: f64 = room.distance(&room2);
+                    let distance: f64 = room.distance(room2);
                     if mindistance.is_none() || distance < mindistance.unwrap() {
                         mindistance = Some(distance);
                         closest = Some(i);
@@ -83,7 +197,7 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                         corridor_h = if corridor_h_min == corridor_h_max {
                             Some(corridor_h_min)
                         } else {
-                            Some(ScaleType::from_usize(rng.gen_range( corridor_h_min.to_usize().unwrap() , corridor_h_max.to_usize().unwrap()  )).expect("Unable to compute corridor H"))
+                            Some(ScaleType::from_usize(corridorrng.gen_range( corridor_h_min.to_usize().unwrap() , corridor_h_max.to_usize().unwrap()  )).expect("Unable to compute corridor H"))
                         };
                     } else if room.left() <= room2.right() && room.right() >= room2.left() {
                         //vertical corridor
@@ -93,7 +207,7 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                         corridor_v = if corridor_v_min == corridor_v_max {
                             Some(corridor_v_min)
                         } else {
-                            Some(ScaleType::from_usize(rng.gen_range( corridor_v_min.to_usize().unwrap() , corridor_v_max.to_usize().unwrap()  )).expect("Unable to compute corridor H"))
+                            Some(ScaleType::from_usize(corridorrng.gen_range( corridor_v_min.to_usize().unwrap() , corridor_v_max.to_usize().unwrap()  )).expect("Unable to compute corridor H"))
                         };
                     }
                     if let Some(corridor_h) = corridor_h {
@@ -103,8 +217,10 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                             (room2.right(), room.left())
                         };
                         for x in range(begin_x, end_x) {
-                            grid.set(&Point(x,corridor_h), ValueType::one());
+                            let point = Point(x,corridor_h);
+                            if !grid.is_set(&point) { grid.set(&point, corridorvalue()); }
                         }
+                        observer(&GenerationEvent::Corridor(Point(begin_x,corridor_h), Point(end_x,corridor_h)), &grid);
                     } else if let Some(corridor_v) = corridor_v {
                         let (begin_y, end_y) = if room.top() < room2.top() {
                             (room.bottom(), room2.top())
@@ -112,13 +228,16 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
                             (room2.bottom(), room.top())
                         };
                         for y in range(begin_y, end_y) {
-                            grid.set(&Point(corridor_v,y), ValueType::one());
+                            let point = Point(corridor_v,y);
+                            if !grid.is_set(&point) { grid.set(&point, corridorvalue()); }
                         }
+                        observer(&GenerationEvent::Corridor(Point(corridor_v,begin_y), Point(corridor_v,end_y)), &grid);
                     } else {
                         //cornered corridors
-                        let from: Point<ScaleType> = room.randompoint(&mut rng);
-                        let to: Point<ScaleType> = room2.randompoint(&mut rng);
-                        grid.rectpathto(&mut rng, &from, &to, ValueType::one());
+                        if let (Ok(from), Ok(to)) = (room.randompoint(&mut corridorrng), room2.randompoint(&mut corridorrng)) {
+                            grid.rectpathto(&mut corridorrng, &from, &to, corridorvalue());
+                            observer(&GenerationEvent::Corridor(from, to), &grid);
+                        }
                     }
                 }
             }
@@ -126,19 +245,49 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
             rooms.push(room);
         }
 
+        if properties.symmetry != Symmetry::None {
+            //connect the closest room to the centre so it connects to its symmetric copies
+            let anchors = properties.symmetry.anchors(width, height);
+            if let Some(anchor) = anchors.first() {
+                if let Some(closest) = grid.nearest(anchor, |_, v| *v != ValueType::zero()) {
+                    grid.rectpathto(&mut corridorrng, &closest, anchor, corridorvalue());
+                    observer(&GenerationEvent::Corridor(closest, *anchor), &grid);
+                }
+            }
+            for anchor in anchors.iter() {
+                if !grid.is_set(anchor) { grid.set(anchor, corridorvalue()); }
+            }
+            grid = grid.symmetric(width, height, properties.symmetry);
+            //every copy of a room gets a number of its own, so copies that touch across the edge of
+            //the sector remain separate rooms (copies that overlap the room itself are part of it)
+            let images: Vec<Vec<Rectangle<ScaleType>>> = rooms.iter().map(|room| properties.symmetry.rectangles(room, width, height)).collect();
+            let mut number = rooms.len();
+            for copy in 1..images.first().map(|images| images.len()).unwrap_or(0) {
+                for (room, images) in rooms.iter().zip(images.iter()) {
+                    if !images[copy].intersects(room) {
+                        number += 1;
+                        for point in images[copy].iter() {
+                            grid.set(&point, roomvalue(number));
+                        }
+                    }
+                }
+            }
+            observer(&GenerationEvent::Symmetry(properties.symmetry), &grid);
+        }
+
         grid
     }
 
     fn render(&self) -> Grid<ScaleType,RenderedTextCell> {
         let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
-        for (i, point) in self.rectangle().iter().enumerate() {
+        for point in self.rectangle().iter() {
             renderedgrid.set(&point,  RoomGrid::rendercell(self, &point) );
         }
         renderedgrid
     }
 
     fn rendercell(&self, point: &Point<ScaleType>) -> RenderedTextCell {
-        if self[&point] != ValueType::zero() {
+        if self[point] != ValueType::zero() {
             RenderedTextCell {
                 background_colour: Some((127,127,127)),
                 foreground_colour: None,
@@ -152,4 +301,175 @@ impl<ScaleType,ValueType> RoomGrid<ScaleType,ValueType> for Grid<ScaleType,Value
             }
         }
     }
+
+    ///Is the cell part of a corridor (rather than a room or wall)?
+    fn corridor(&self, point: &Point<ScaleType>) -> bool {
+        self[point] == corridorvalue()
+    }
+
+    ///Returns all rooms with their room number. Every room the generator placed has a number of
+    ///its own, also the copies made by symmetry, so every rectangle of cells sharing a room number
+    ///is a room.
+    fn rooms(&self) -> Vec<(ValueType,Rectangle<ScaleType>)> {
+        let mut rooms = Vec::new();
+        let mut visited: Grid<ScaleType,bool> = Grid::new(self.width(), self.height());
+        for (point, value) in self.iter() {
+            if *value == ValueType::zero() || self.corridor(&point) || visited[&point] {
+                continue;
+            }
+            //rooms are rectangles, so the room extends right and down from its first (top-left) cell
+            let mut right = point;
+            while let Some(next) = self.getneighbour(&right, Direction::East).filter(|next| self[next] == *value) {
+                right = next;
+            }
+            let mut bottomright = right;
+            while let Some(next) = self.getneighbour(&bottomright, Direction::South).filter(|next| self[next] == *value) {
+                bottomright = next;
+            }
+            let room = Rectangle::new(&point, &bottomright);
+            for roompoint in room.iter() {
+                visited.set(&roompoint, true);
+            }
+            rooms.push((*value, room));
+        }
+        rooms
+    }
+
+    ///Renders the map as vector graphics, rooms are drawn as filled rectangles and corridors as
+    ///paths through the centres of their cells
+    fn svg(&self, cellsize: f64) -> Svg {
+        let mut svg = Svg::new(self.width_as_usize() as f64 * cellsize, self.height_as_usize() as f64 * cellsize);
+        svg.rect(0.0, 0.0, svg.width, svg.height, (0,0,0));
+        for (_, room) in RoomGrid::rooms(self).iter() {
+            svg.rect(room.left().to_f64().unwrap() * cellsize, room.top().to_f64().unwrap() * cellsize,
+                     room.width().to_f64().unwrap() * cellsize, room.height().to_f64().unwrap() * cellsize, (127,127,127));
+        }
+        let centre = |point: &Point<ScaleType>| ((point.xs() as f64 + 0.5) * cellsize, (point.ys() as f64 + 0.5) * cellsize);
+        let mut segments = Vec::new();
+        for (point, v) in self.iter() {
+            if *v == ValueType::zero() {
+                continue;
+            }
+            //connect corridor cells to their neighbours (corridors or rooms), looking only east and
+            //south so every connection is drawn once
+            for direction in [Direction::East, Direction::South].iter() {
+                if let Some(neighbour) = self.getneighbour(&point, *direction) {
+                    if self[&neighbour] != ValueType::zero() && (self.corridor(&point) || self.corridor(&neighbour)) {
+                        segments.push((centre(&point), centre(&neighbour)));
+                    }
+                }
+            }
+            //an isolated corridor cell is drawn as a zero-length segment, which the round cap turns into a dot
+            if self.corridor(&point) && self.countneighbours(&point) == 0 {
+                segments.push((centre(&point), centre(&point)));
+            }
+        }
+        svg.lines(&segments, (127,127,127), cellsize);
+        svg
+    }
+
+    ///Exports the map as a Tiled map with tiles for room floors, corridors and walls (rock that
+    ///borders on a room or corridor, diagonals included), all other rock is left empty. The rooms
+    ///are included as rectangle objects.
+    fn tiled(&self, tilesize: usize) -> TiledMap {
+        let mut map = TiledMap::new(self.width_as_usize(), self.height_as_usize(), tilesize);
+        let tile = |colour| RenderedTextCell { background_colour: Some(colour), foreground_colour: None, text: None };
+        let floor = map.addtile("floor", tile((127,127,127)));
+        let corridor = map.addtile("corridor", tile((100,100,100)));
+        let wall = map.addtile("wall", tile((64,48,32)));
+        for (point, v) in self.iter() {
+            if self.corridor(&point) {
+                map.set(&point, corridor);
+            } else if *v != ValueType::zero() {
+                map.set(&point, floor);
+            } else {
+                let (x, y) = (point.xs() as isize, point.ys() as isize);
+                let bordersfloor = (-1..=1).any(|dy| (-1..=1).any(|dx| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    nx >= 0 && ny >= 0 && (nx as usize) < self.width_as_usize() && (ny as usize) < self.height_as_usize()
+                        && self.is_set(&Point::new_usize(nx as usize, ny as usize))
+                }));
+                if bordersfloor {
+                    map.set(&point, wall);
+                }
+            }
+        }
+        let rooms = RoomGrid::rooms(self).into_iter().map(|(number, room)| TiledObject {
+            name: format!("room {}", number.to_usize().unwrap()),
+            kind: "room".to_string(),
+            x: room.left().to_usize().unwrap(),
+            y: room.top().to_usize().unwrap(),
+            width: room.width().to_usize().unwrap(),
+            height: room.height().to_usize().unwrap(),
+        }).collect();
+        map.objectgroups.push(ObjectGroup { name: "rooms".to_string(), objects: rooms });
+        map
+    }
+}
+
+///Generates rooms and corridors through `RoomGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct RoomGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for RoomGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = RoomGridProperties;
+
+    fn name(&self) -> &'static str {
+        "rooms"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rectangular rooms connected by corridors"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("rooms", "Number of rooms", Some("6")),
+            ParameterInfo::new("symmetry", "Symmetry to enforce: none, horizontal, vertical, rotate2, rotate4", Some("none")),
+        )
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<RoomGridProperties, Error> {
+        Ok(RoomGridProperties {
+            rooms: parameter(parameters, "rooms")?,
+            symmetry: parameter(parameters, "symmetry")?,
+        })
+    }
+
+    fn validate(&self, width: ScaleType, height: ScaleType, properties: &RoomGridProperties) -> Result<(), Error> {
+        properties.validate(width.to_usize().ok_or(Error::OutOfBounds)?, height.to_usize().ok_or(Error::OutOfBounds)?)
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &RoomGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as RoomGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone()))
+    }
+
+    fn generate_with_events(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &RoomGridProperties, observer: &mut Observer<ScaleType,ValueType>) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Ok(<Grid<ScaleType,ValueType> as RoomGrid<ScaleType,ValueType>>::generate_with_events(width, height, seed, properties.clone(), observer))
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, _style: &str, _properties: &RoomGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        RoomGrid::render(grid)
+    }
+
+    fn svg(&self, grid: &Grid<ScaleType,ValueType>, cellsize: f64, _style: &str) -> Option<Svg> {
+        Some(RoomGrid::svg(grid, cellsize))
+    }
+
+    fn tiled(&self, grid: &Grid<ScaleType,ValueType>, tilesize: usize, _style: &str) -> Option<TiledMap> {
+        Some(RoomGrid::tiled(grid, tilesize))
+    }
+
+    fn describe(&self, grid: &Grid<ScaleType,ValueType>, point: &Point<ScaleType>) -> String {
+        if grid[point] == ValueType::zero() {
+            "wall".to_string()
+        } else if grid.corridor(point) {
+            format!("corridor (value {})", grid[point].to_usize().unwrap())
+        } else {
+            format!("room {}", grid[point].to_usize().unwrap())
+        }
+    }
 }
@@ -0,0 +1,74 @@
+use rand::SeedableRng;
+
+///A seed that can be split into independent seeds for the phases of a generator, so that a change
+///in how much randomness one phase uses does not change the others:
+///
+///```
+///use procmapgen::seed::Seed;
+///use rand_pcg::Pcg32;
+///
+///let seed = Seed::parse("castle-42");
+///let mut rooms: Pcg32 = seed.rng("rooms");
+///let mut corridors: Pcg32 = seed.rng("corridors");
+///```
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    ///Numbers are used as they are, any other text is hashed (with a hash that does not depend on
+    ///the platform or the version of Rust)
+    pub fn parse(text: &str) -> Seed {
+        match text.parse::<u64>() {
+            Ok(seed) => Seed(seed),
+            Err(_) => Seed(fnv(text.as_bytes())),
+        }
+    }
+
+    ///Derives the seed of a phase or part by its name, which can be split further
+    pub fn split(&self, label: &str) -> Seed {
+        Seed(splitmix(splitmix(self.0) ^ fnv(label.as_bytes())))
+    }
+
+    ///A random number generator of any seedable type for a phase or part, see `split()`
+    pub fn rng<R: SeedableRng>(&self, label: &str) -> R {
+        R::seed_from_u64(self.split(label).0)
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(seed: u64) -> Seed {
+        Seed(seed)
+    }
+}
+
+///64-bit FNV-1a hash
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+///The SplitMix64 finalizer, so that nearby seeds give unrelated seeds
+fn splitmix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Seed::parse("42"), Seed(42));
+        //FNV-1a of the text
+        assert_eq!(Seed::parse("castle-42"), Seed(11007556009697132510));
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(Seed::parse("castle-42").split("rooms"), Seed(12290811883373546414));
+        assert_eq!(Seed(42).split("rooms"), Seed(9674803366490315375));
+        assert_ne!(Seed(42).split("rooms"), Seed(42).split("corridors"));
+    }
+}
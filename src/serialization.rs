@@ -0,0 +1,329 @@
+//! Lossless saving and loading of grids.
+//!
+//! # Binary format
+//!
+//! All numbers are little endian:
+//!
+//! | offset | size | content                                                    |
+//! |--------|------|------------------------------------------------------------|
+//! | 0      | 4    | magic bytes `PMAP`                                         |
+//! | 4      | 1    | format version, currently 1                                |
+//! | 5      | 1    | type code of the scale type (see [`GridNumber::TYPECODE`])  |
+//! | 6      | 1    | type code of the value type                                |
+//! | 7      | 1    | reserved, 0                                                |
+//! | 8      | 8    | width (u64)                                                |
+//! | 16     | 8    | height (u64)                                               |
+//! | 24     | n    | the values, row by row, each in the size of the value type |
+//! | 24 + n | 4    | CRC-32 checksum over all preceding bytes (u32)             |
+//!
+//! # Text formats
+//!
+//! * CSV: one line per row, values separated by commas.
+//! * PGM: greyscale images (for height maps), written as binary PGM (P5) with the maximum value
+//!   of the grid as maximum grey value, so no precision is lost. Both plain (P2) and binary (P5)
+//!   PGM can be read.
+
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self,Read,Write,BufRead,BufReader,BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+use crc32fast;
+
+use crate::grid::{Grid,GenericGrid};
+
+const MAGIC: &[u8;4] = b"PMAP";
+const VERSION: u8 = 1;
+const HEADERSIZE: usize = 24;
+
+///A primitive number type that can be stored in a serialized grid
+pub trait GridNumber: Copy + Display + FromStr + ToPrimitive + FromPrimitive {
+    ///Identifies the type in the binary format: 1-4 are u8 to u64, 5-8 are i8 to i64, 9 and 10
+    ///are f32 and f64
+    const TYPECODE: u8;
+    ///Size in bytes
+    const SIZE: usize;
+    fn write_le(&self, buffer: &mut Vec<u8>);
+    ///Reads a value from a slice of exactly SIZE bytes
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! gridnumber {
+    ($type:ty, $code:expr) => {
+        impl GridNumber for $type {
+            const TYPECODE: u8 = $code;
+            const SIZE: usize = std::mem::size_of::<$type>();
+            fn write_le(&self, buffer: &mut Vec<u8>) {
+                buffer.extend_from_slice(&self.to_le_bytes());
+            }
+            fn read_le(bytes: &[u8]) -> Self {
+                let mut array = [0; std::mem::size_of::<$type>()];
+                array.copy_from_slice(bytes);
+                <$type>::from_le_bytes(array)
+            }
+        }
+    };
+}
+
+gridnumber!(u8, 1);
+gridnumber!(u16, 2);
+gridnumber!(u32, 3);
+gridnumber!(u64, 4);
+gridnumber!(i8, 5);
+gridnumber!(i16, 6);
+gridnumber!(i32, 7);
+gridnumber!(i64, 8);
+gridnumber!(f32, 9);
+gridnumber!(f64, 10);
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub trait SerializableGrid<ScaleType, ValueType>: Sized {
+    fn write_binary(&self, writer: &mut impl Write) -> io::Result<()>;
+    fn read_binary(reader: &mut impl Read) -> io::Result<Self>;
+    fn write_csv(&self, writer: &mut impl Write) -> io::Result<()>;
+    fn read_csv(reader: impl BufRead) -> io::Result<Self>;
+    fn write_pgm(&self, writer: &mut impl Write) -> io::Result<()>;
+    fn read_pgm(reader: &mut impl Read) -> io::Result<Self>;
+    fn save(&self, filename: &str) -> io::Result<()>;
+    fn load(filename: &str) -> io::Result<Self>;
+}
+
+impl<ScaleType,ValueType> SerializableGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy + GridNumber,
+    ValueType: Default + PartialEq + Clone + GridNumber {
+
+    ///Writes the grid in the binary format described in the module documentation
+    fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(HEADERSIZE + self.get_data_vec().len() * ValueType::SIZE + 4);
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&[VERSION, ScaleType::TYPECODE, ValueType::TYPECODE, 0]);
+        buffer.extend_from_slice(&(self.width_as_usize() as u64).to_le_bytes());
+        buffer.extend_from_slice(&(self.height_as_usize() as u64).to_le_bytes());
+        for value in self.get_data_vec().iter() {
+            value.write_le(&mut buffer);
+        }
+        let checksum = crc32fast::hash(&buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+        writer.write_all(&buffer)
+    }
+
+    ///Reads a grid in the binary format, the scale and value types must match those in the header
+    fn read_binary(reader: &mut impl Read) -> io::Result<Self> {
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        if buffer.len() < HEADERSIZE + 4 || &buffer[0..4] != MAGIC {
+            return Err(invalid("Not a grid file".to_string()));
+        }
+        if buffer[4] != VERSION {
+            return Err(invalid(format!("Unsupported grid format version {}", buffer[4])));
+        }
+        if buffer[5] != ScaleType::TYPECODE || buffer[6] != ValueType::TYPECODE {
+            return Err(invalid(format!("Grid has scale type {} and value type {}, expected {} and {}",
+                                       buffer[5], buffer[6], ScaleType::TYPECODE, ValueType::TYPECODE)));
+        }
+        let (content, checksum) = buffer.split_at(buffer.len() - 4);
+        if crc32fast::hash(content) != u32::read_le(checksum) {
+            return Err(invalid("Checksum mismatch, grid file is corrupted".to_string()));
+        }
+        let width = u64::read_le(&content[8..16]);
+        let height = u64::read_le(&content[16..24]);
+        let data = &content[HEADERSIZE..];
+        let expected = (width as usize).checked_mul(height as usize).and_then(|cells| cells.checked_mul(ValueType::SIZE));
+        if expected != Some(data.len()) {
+            return Err(invalid(format!("Grid data does not match dimensions {}x{}", width, height)));
+        }
+        let mut grid = newgrid(width as usize, height as usize)?;
+        for (value, bytes) in grid.get_mut_data_vec().iter_mut().zip(data.chunks(ValueType::SIZE)) {
+            *value = ValueType::read_le(bytes);
+        }
+        Ok(grid)
+    }
+
+    fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+        for row in self.get_data_vec().chunks(self.width_as_usize().max(1)) {
+            let row: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+            writeln!(writer, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+
+    ///Reads a grid from CSV, all rows must have the same number of values
+    fn read_csv(reader: impl BufRead) -> io::Result<Self> {
+        let mut values: Vec<ValueType> = Vec::new();
+        let mut width: Option<usize> = None;
+        let mut height = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = line.split(',').map(|field| field.trim().parse::<ValueType>().map_err(|_| invalid(format!("Invalid value on line {}: {}", height + 1, field.trim()))))
+                          .collect::<io::Result<Vec<ValueType>>>()?;
+            if let Some(width) = width.filter(|width| *width != row.len()) {
+                return Err(invalid(format!("Line {} has {} values, expected {}", height + 1, row.len(), width)));
+            }
+            width = Some(row.len());
+            values.extend(row);
+            height += 1;
+        }
+        let mut grid = newgrid(width.unwrap_or(0), height)?;
+        *grid.get_mut_data_vec() = values;
+        Ok(grid)
+    }
+
+    ///Writes the grid as binary PGM (P5), fails for negative or fractional values and values above 65535
+    fn write_pgm(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut values: Vec<u16> = Vec::with_capacity(self.get_data_vec().len());
+        for value in self.get_data_vec().iter() {
+            match value.to_u16() {
+                Some(v) if v.to_f64() == value.to_f64() => values.push(v),
+                _ => return Err(invalid(format!("Value {} can not be stored in a PGM", value))),
+            }
+        }
+        let maxval = values.iter().copied().max().unwrap_or(0).max(1);
+        write!(writer, "P5\n{} {}\n{}\n", self.width_as_usize(), self.height_as_usize(), maxval)?;
+        let data: Vec<u8> = if maxval < 256 {
+            values.iter().map(|v| *v as u8).collect()
+        } else {
+            values.iter().flat_map(|v| v.to_be_bytes().to_vec()).collect()
+        };
+        writer.write_all(&data)
+    }
+
+    ///Reads a plain (P2) or binary (P5) PGM, the grey values are taken as they are
+    fn read_pgm(reader: &mut impl Read) -> io::Result<Self> {
+        let mut buffer: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        //parse the header: magic, width, height and maximum value, separated by whitespace and
+        //possibly interspersed with comments
+        let mut fields: Vec<String> = Vec::new();
+        let mut pos = 0;
+        while fields.len() < 4 && pos < buffer.len() {
+            if buffer[pos] == b'#' {
+                while pos < buffer.len() && buffer[pos] != b'\n' { pos += 1; }
+            } else if buffer[pos].is_ascii_whitespace() {
+                pos += 1;
+            } else {
+                let start = pos;
+                while pos < buffer.len() && !buffer[pos].is_ascii_whitespace() { pos += 1; }
+                fields.push(String::from_utf8_lossy(&buffer[start..pos]).to_string());
+            }
+        }
+        if fields.len() < 4 || (fields[0] != "P2" && fields[0] != "P5") {
+            return Err(invalid("Not a PGM file".to_string()));
+        }
+        let number = |field: &str| field.parse::<usize>().map_err(|_| invalid(format!("Invalid number in PGM header: {}", field)));
+        let (width, height, maxval) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+        let mut values: Vec<usize> = Vec::with_capacity(width * height);
+        if fields[0] == "P5" {
+            //exactly one whitespace character separates the header from the data
+            let data = buffer.get(pos + 1..).unwrap_or(&[]);
+            if maxval < 256 {
+                values.extend(data.iter().map(|v| *v as usize));
+            } else {
+                values.extend(data.chunks_exact(2).map(|v| u16::from_be_bytes([v[0], v[1]]) as usize));
+            }
+            values.truncate(width * height);
+        } else {
+            for field in String::from_utf8_lossy(&buffer[pos..]).split_whitespace() {
+                values.push(number(field)?);
+            }
+        }
+        if values.len() != width * height {
+            return Err(invalid(format!("PGM data does not match dimensions {}x{}", width, height)));
+        }
+        let mut grid = newgrid(width, height)?;
+        for (value, v) in grid.get_mut_data_vec().iter_mut().zip(values) {
+            *value = ValueType::from_usize(v).ok_or_else(|| invalid(format!("Value {} out of range", v)))?;
+        }
+        Ok(grid)
+    }
+
+    ///Saves the grid to file, the format is determined by the extension: .csv, .pgm or anything
+    ///else for the binary format
+    fn save(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => self.write_csv(&mut writer),
+            Some("pgm") => self.write_pgm(&mut writer),
+            _ => self.write_binary(&mut writer),
+        }?;
+        writer.flush()
+    }
+
+    ///Loads a grid from file, the format is determined by the extension like in `save()`
+    fn load(filename: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(filename)?);
+        match Path::new(filename).extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Self::read_csv(reader),
+            Some("pgm") => Self::read_pgm(&mut reader),
+            _ => Self::read_binary(&mut reader),
+        }
+    }
+}
+
+///Creates an empty grid, checking that the dimensions fit in the scale type
+fn newgrid<ScaleType,ValueType>(width: usize, height: usize) -> io::Result<Grid<ScaleType,ValueType>> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy,
+    ValueType: Default + PartialEq + Clone {
+    match (ScaleType::from_usize(width), ScaleType::from_usize(height)) {
+        (Some(width), Some(height)) => Ok(Grid::new(width, height)),
+        _ => Err(invalid(format!("Grid dimensions {}x{} are too large", width, height))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    fn grid() -> Grid<u16,u8> {
+        let mut grid: Grid<u16,u8> = Grid::new(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                grid.set(&Point(x,y), (x * 10 + y) as u8);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let grid = grid();
+        let mut buffer: Vec<u8> = Vec::new();
+        grid.write_binary(&mut buffer).unwrap();
+        let copy: Grid<u16,u8> = Grid::read_binary(&mut buffer.as_slice()).unwrap();
+        assert!(copy == grid);
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let grid = grid();
+        let mut buffer: Vec<u8> = Vec::new();
+        grid.write_csv(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), "0,10,20,30\n1,11,21,31\n2,12,22,32\n");
+        let copy: Grid<u16,u8> = Grid::read_csv(buffer.as_slice()).unwrap();
+        assert!(copy == grid);
+    }
+
+    #[test]
+    fn pgm_roundtrip() {
+        let grid = grid();
+        let mut buffer: Vec<u8> = Vec::new();
+        grid.write_pgm(&mut buffer).unwrap();
+        let copy: Grid<u16,u8> = Grid::read_pgm(&mut buffer.as_slice()).unwrap();
+        assert!(copy == grid);
+
+        //values above 255 take two bytes per value
+        let mut grid: Grid<u16,u16> = Grid::new(3, 2);
+        grid.set(&Point(2,1), 1000);
+        let mut buffer: Vec<u8> = Vec::new();
+        grid.write_pgm(&mut buffer).unwrap();
+        let copy: Grid<u16,u16> = Grid::read_pgm(&mut buffer.as_slice()).unwrap();
+        assert!(copy == grid);
+    }
+}
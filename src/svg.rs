@@ -0,0 +1,71 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self,Write,BufWriter};
+
+///A straight line segment between two (x,y) coordinates
+pub type Segment = ((f64,f64),(f64,f64));
+
+///A minimal SVG document, elements are kept as serialised strings in drawing order
+#[derive(Debug,Clone,PartialEq)]
+pub struct Svg {
+    pub width: f64,
+    pub height: f64,
+    pub elements: Vec<String>,
+}
+
+///Formats a colour as an SVG/HTML hex colour
+pub fn hexcolour(colour: (u8,u8,u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour.0, colour.1, colour.2)
+}
+
+///Rounds coordinates to two decimals to keep the output compact
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+impl Svg {
+    pub fn new(width: f64, height: f64) -> Svg {
+        Svg {
+            width,
+            height,
+            elements: Vec::new(),
+        }
+    }
+
+    ///Adds a filled rectangle
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, fill: (u8,u8,u8)) {
+        self.elements.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", round(x), round(y), round(width), round(height), hexcolour(fill)));
+    }
+
+    ///Adds a filled circle
+    pub fn circle(&mut self, x: f64, y: f64, radius: f64, fill: (u8,u8,u8)) {
+        self.elements.push(format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>", round(x), round(y), round(radius), hexcolour(fill)));
+    }
+
+    ///Adds a stroked path consisting of straight line segments. Caps and joins are rounded so
+    ///touching segments form smooth connections.
+    pub fn lines(&mut self, segments: &[Segment], stroke: (u8,u8,u8), strokewidth: f64) {
+        if segments.is_empty() {
+            return;
+        }
+        let data: Vec<String> = segments.iter().map(|((x1,y1),(x2,y2))| format!("M{} {}L{} {}", round(*x1), round(*y1), round(*x2), round(*y2))).collect();
+        self.elements.push(format!("<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>", data.join(""), hexcolour(stroke), round(strokewidth)));
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        write!(writer, "{}", self)?;
+        writer.flush()
+    }
+}
+
+impl fmt::Display for Svg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(f, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">", w = self.width, h = self.height)?;
+        for element in self.elements.iter() {
+            writeln!(f, "  {}", element)?;
+        }
+        writeln!(f, "</svg>")
+    }
+}
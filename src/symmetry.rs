@@ -0,0 +1,124 @@
+use num::{Integer,FromPrimitive,ToPrimitive};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::point::Point;
+use crate::rectangle::Rectangle;
+
+///The symmetry to enforce on a generated map. Generation runs on a single sector of the
+///grid which is then reflected or rotated onto the rest.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub enum Symmetry {
+    #[default]
+    None,
+    ///The left half is mirrored onto the right half
+    Horizontal,
+    ///The top half is mirrored onto the bottom half
+    Vertical,
+    ///The left half is rotated 180 degrees onto the right half
+    Rotational2,
+    ///The top-left quadrant is rotated 90 degrees onto the others. On non-square grids the
+    ///quadrant is mirrored instead, so all four quadrants still remain equivalent.
+    Rotational4,
+}
+
+impl Symmetry {
+
+    ///Returns the dimensions of the sector that is actually generated, the sector is always
+    ///anchored at the top-left of the grid
+    pub fn sector<ScaleType>(&self, width: ScaleType, height: ScaleType) -> (ScaleType, ScaleType) where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+
+        let two = ScaleType::from_u8(2).unwrap();
+        let halfwidth = (width + ScaleType::one()) / two;
+        let halfheight = (height + ScaleType::one()) / two;
+        match self {
+            Symmetry::None => (width, height),
+            Symmetry::Horizontal | Symmetry::Rotational2 => (halfwidth, height),
+            Symmetry::Vertical => (width, halfheight),
+            Symmetry::Rotational4 => (halfwidth, halfheight),
+        }
+    }
+
+    ///Returns all points that are equivalent to the given point under this symmetry, including
+    ///the point itself
+    pub fn points<ScaleType>(&self, point: &Point<ScaleType>, width: ScaleType, height: ScaleType) -> Vec<Point<ScaleType>> where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+
+        let maxx = width - ScaleType::one();
+        let maxy = height - ScaleType::one();
+        let (x, y) = (point.x(), point.y());
+        match self {
+            Symmetry::None => vec!(*point),
+            Symmetry::Horizontal => vec!(*point, Point(maxx - x, y)),
+            Symmetry::Vertical => vec!(*point, Point(x, maxy - y)),
+            Symmetry::Rotational2 => vec!(*point, Point(maxx - x, maxy - y)),
+            Symmetry::Rotational4 if width == height => vec!(*point, Point(maxx - y, x), Point(maxx - x, maxy - y), Point(y, maxy - x)),
+            Symmetry::Rotational4 => vec!(*point, Point(maxx - x, y), Point(x, maxy - y), Point(maxx - x, maxy - y)),
+        }
+    }
+
+    ///Returns the rectangles that are equivalent to the given rectangle under this symmetry, in the
+    ///same order as `points()`, so the first is the rectangle itself
+    pub fn rectangles<ScaleType>(&self, rectangle: &Rectangle<ScaleType>, width: ScaleType, height: ScaleType) -> Vec<Rectangle<ScaleType>> where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+
+        let topleft = self.points(&rectangle.topleft, width, height);
+        let bottomright = self.points(&rectangle.bottomright, width, height);
+        topleft.iter().zip(bottomright.iter()).map(|(a, b)| {
+            Rectangle::new(&Point(a.x().min(b.x()), a.y().min(b.y())), &Point(a.x().max(b.x()), a.y().max(b.y())))
+        }).collect()
+    }
+
+    ///Returns the cells at the centre of the grid that lie within the sector. The centre maps onto
+    ///itself under every symmetry, so if the generated sector connects to all of these cells, all
+    ///copies of the sector will be connected to each other.
+    pub fn anchors<ScaleType>(&self, width: ScaleType, height: ScaleType) -> Vec<Point<ScaleType>> where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+
+        if *self == Symmetry::None {
+            return vec!();
+        }
+        let two = ScaleType::from_u8(2).unwrap();
+        let (sectorwidth, sectorheight) = self.sector(width, height);
+        let xs = [(width - ScaleType::one()) / two, width / two];
+        let ys = [(height - ScaleType::one()) / two, height / two];
+        let mut anchors: Vec<Point<ScaleType>> = Vec::new();
+        for y in ys.iter() {
+            for x in xs.iter() {
+                let point = Point(*x,*y);
+                if *x < sectorwidth && *y < sectorheight && !anchors.contains(&point) {
+                    anchors.push(point);
+                }
+            }
+        }
+        anchors
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Symmetry::None),
+            "horizontal" => Ok(Symmetry::Horizontal),
+            "vertical" => Ok(Symmetry::Vertical),
+            "rotate2" => Ok(Symmetry::Rotational2),
+            "rotate4" => Ok(Symmetry::Rotational4),
+            _ => Err(format!("No such symmetry: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Symmetry::None => "none",
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Rotational2 => "rotate2",
+            Symmetry::Rotational4 => "rotate4",
+        })
+    }
+}
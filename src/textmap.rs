@@ -0,0 +1,161 @@
+use num::{Integer,FromPrimitive,ToPrimitive,Bounded};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::common::Direction;
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid};
+use crate::pipegrid::pipearms;
+use crate::error::Error;
+
+///Connections of a pipe cell as a bitmask
+pub const NORTH: u8 = 1;
+pub const EAST: u8 = 2;
+pub const SOUTH: u8 = 4;
+pub const WEST: u8 = 8;
+
+///Returns the bitmask for the neighbours (north, east, south, west) a pipe cell connects to
+pub fn connectionmask(north: bool, east: bool, south: bool, west: bool) -> u8 {
+    (north as u8) * NORTH + (east as u8) * EAST + (south as u8) * SOUTH + (west as u8) * WEST
+}
+
+///The inverse of `connectionmask()`: returns whether a cell connects to its north, east, south and
+///west neighbours
+pub fn connectionarms(mask: u8) -> (bool, bool, bool, bool) {
+    (mask & NORTH != 0, mask & EAST != 0, mask & SOUTH != 0, mask & WEST != 0)
+}
+
+///Parses a character table like `#=0,.=1,+=2`, i.e. comma separated pairs of a character and
+///the value it stands for
+pub fn parsechartable<ValueType: FromStr>(spec: &str) -> Result<HashMap<char,ValueType>, Error> {
+    let mut table = HashMap::new();
+    for entry in spec.split(',') {
+        let mut chars = entry.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), Some('=')) => {
+                let value = chars.as_str().parse::<ValueType>().map_err(|_| Error::Parse(format!("Invalid value in character table: {}", entry)))?;
+                table.insert(c, value);
+            },
+            _ => return Err(Error::Parse(format!("Invalid entry in character table, expected char=value: {}", entry))),
+        }
+    }
+    Ok(table)
+}
+
+///Splits text into lines of characters, trailing empty lines are ignored
+fn textlines(text: &str) -> Vec<Vec<char>> {
+    let mut lines: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn newgrid<ScaleType,ValueType>(lines: &[Vec<char>]) -> Result<Grid<ScaleType,ValueType>, Error> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy,
+    ValueType: Default + PartialEq + Clone {
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    match (ScaleType::from_usize(width), ScaleType::from_usize(lines.len())) {
+        (Some(width), Some(height)) => Ok(Grid::new(width, height)),
+        _ => Err(Error::Parse(format!("Text map of {}x{} is too large", width, lines.len()))),
+    }
+}
+
+pub trait TextGrid<ScaleType, ValueType>: Sized {
+    fn from_text(text: &str, table: &HashMap<char,ValueType>, default: Option<ValueType>) -> Result<Self, Error>;
+    fn from_pipetext(text: &str) -> Result<(Self, Grid<ScaleType,u8>), Error>;
+}
+
+impl<ScaleType,ValueType> TextGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded + Copy,
+    ValueType: Default + PartialEq + Clone + FromPrimitive {
+
+    ///Reads a text map, every character is looked up in the table. Characters that are not in the
+    ///table get the default value, or are an error if there is no default. Lines shorter than the
+    ///longest line are padded with the default (or the default value of the type).
+    fn from_text(text: &str, table: &HashMap<char,ValueType>, default: Option<ValueType>) -> Result<Self, Error> {
+        let lines = textlines(text);
+        let mut grid: Grid<ScaleType,ValueType> = newgrid(&lines)?;
+        if let Some(default) = default.as_ref() {
+            for value in grid.get_mut_data_vec().iter_mut() {
+                *value = default.clone();
+            }
+        }
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.iter().enumerate() {
+                let value = match (table.get(c), default.as_ref()) {
+                    (Some(value), _) | (None, Some(value)) => value.clone(),
+                    (None, None) => return Err(Error::Parse(format!("Unknown character '{}' on line {}, column {}", c, y + 1, x + 1))),
+                };
+                grid.set(&Point::new_usize(x, y), value);
+            }
+        }
+        Ok(grid)
+    }
+
+    ///Reads a pipe network drawn with box-drawing characters, as rendered by `PipeGrid`. Returns
+    ///a grid with values as `PipeGrid` generates them (2 for the heavy backbone, 4 for other
+    ///pipes, 0 for spaces) and a grid with the connections of every cell as drawn (a bitmask of
+    ///NORTH, EAST, SOUTH and WEST), which `PipeGrid::render_with_connections` and
+    ///`PipeGrid::deadends_with_connections` take. Full blocks (the thick render style) are
+    ///connected to all neighbouring pipe cells.
+    fn from_pipetext(text: &str) -> Result<(Self, Grid<ScaleType,u8>), Error> {
+        let lines = textlines(text);
+        let mut grid: Grid<ScaleType,ValueType> = newgrid(&lines)?;
+        let mut connections: Grid<ScaleType,u8> = newgrid(&lines)?;
+        let mut blocks: Vec<Point<ScaleType>> = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.iter().enumerate() {
+                let point = Point::new_usize(x, y);
+                if *c == ' ' {
+                    continue;
+                } else if *c == '█' {
+                    grid.set(&point, ValueType::from_u8(4).unwrap());
+                    blocks.push(point);
+                } else if let Some(([north, east, south, west], heavy)) = pipearms(*c) {
+                    grid.set(&point, ValueType::from_u8(if heavy { 2 } else { 4 }).unwrap());
+                    connections.set(&point, connectionmask(north, east, south, west));
+                } else {
+                    return Err(Error::Parse(format!("Not a pipe character: '{}' on line {}, column {}", c, y + 1, x + 1)));
+                }
+            }
+        }
+        for point in blocks.iter() {
+            let mut mask = 0;
+            for (direction, bit) in [(Direction::North, NORTH), (Direction::East, EAST), (Direction::South, SOUTH), (Direction::West, WEST)].iter() {
+                if grid.hasneighbour(point, *direction) {
+                    mask |= bit;
+                }
+            }
+            connections.set(point, mask);
+        }
+        Ok((grid, connections))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipegrid::{PipeGrid,PipeGridProperties,PipeRenderStyle};
+    use crate::colour::{ColourMode,PaintGrid};
+
+    #[test]
+    fn pipetext_roundtrip() {
+        let properties = PipeGridProperties::builder(40, 20).build().unwrap();
+        let grid = <Grid<u16,u8> as PipeGrid<u16,u8>>::generate(40, 20, 42, properties);
+        let text = PipeGrid::render(&grid, PipeRenderStyle::Thin).paint(ColourMode::None);
+        let (copy, connections): (Grid<u16,u8>, _) = Grid::from_pipetext(&text).unwrap();
+        assert!(connections == PipeGrid::connections(&grid));
+        assert_eq!(copy.render_with_connections(&connections, PipeRenderStyle::Thin).paint(ColourMode::None), text);
+        assert_eq!(copy.deadends_with_connections(&connections), grid.deadends());
+    }
+
+    #[test]
+    fn pipetext_keeps_connections() {
+        //two parallel pipes that are drawn apart are not connected
+        let text = "╶──╴\n╶──╴\n";
+        let (grid, connections): (Grid<u16,u8>, _) = Grid::from_pipetext(text).unwrap();
+        assert_eq!(grid.render_with_connections(&connections, PipeRenderStyle::Thin).paint(ColourMode::None), text);
+        assert_eq!(grid.deadends_with_connections(&connections).len(), 4);
+    }
+}
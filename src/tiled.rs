@@ -0,0 +1,261 @@
+use num::{Integer,FromPrimitive,ToPrimitive};
+use std::fs::File;
+use std::io::{self,Write,BufWriter};
+use std::path::Path;
+use serde_json::json;
+
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::image::{Image,RasterGrid};
+use crate::html::escape;
+use crate::point::Point;
+
+///An object on an object layer, coordinates and dimensions are in cells. Objects without width and
+///height are point objects, placed at the centre of their cell.
+#[derive(Debug,Clone,PartialEq)]
+pub struct TiledObject {
+    pub name: String,
+    pub kind: String,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct ObjectGroup {
+    pub name: String,
+    pub objects: Vec<TiledObject>,
+}
+
+///A map for the Tiled map editor (<https://www.mapeditor.org>), with a single tile layer, a
+///generated tileset and any number of object layers
+#[derive(Debug,Clone,PartialEq)]
+pub struct TiledMap {
+    pub width: usize,
+    pub height: usize,
+    ///width and height of a tile in pixels
+    pub tilesize: usize,
+    ///the appearance of each tile in the tileset, tile i has global tile ID i+1
+    pub tiles: Vec<RenderedTextCell>,
+    ///the kind of each tile in the tileset, stored as a custom property
+    pub tilekinds: Vec<String>,
+    ///global tile IDs of the tile layer, row by row, 0 is an empty cell
+    pub data: Vec<u32>,
+    pub objectgroups: Vec<ObjectGroup>,
+}
+
+///Number of tiles per row in the tileset image
+const TILESETCOLUMNS: usize = 16;
+
+impl TiledMap {
+    pub fn new(width: usize, height: usize, tilesize: usize) -> TiledMap {
+        TiledMap {
+            width,
+            height,
+            tilesize,
+            tiles: Vec::new(),
+            tilekinds: Vec::new(),
+            data: vec![0; width * height],
+            objectgroups: Vec::new(),
+        }
+    }
+
+    ///Adds a tile to the tileset and returns its global tile ID
+    pub fn addtile(&mut self, kind: &str, appearance: RenderedTextCell) -> u32 {
+        self.tiles.push(appearance);
+        self.tilekinds.push(kind.to_string());
+        self.tiles.len() as u32
+    }
+
+    pub fn set<ScaleType>(&mut self, point: &Point<ScaleType>, gid: u32) where
+        ScaleType: Integer + FromPrimitive + ToPrimitive + Copy {
+        self.data[point.ys() * self.width + point.xs()] = gid;
+    }
+
+    fn tilesetcolumns(&self) -> usize {
+        self.tiles.len().clamp(1, TILESETCOLUMNS)
+    }
+
+    ///Draws all tiles of the tileset into a single image
+    pub fn tilesetimage(&self) -> Image {
+        let columns = self.tilesetcolumns();
+        let rows = self.tiles.len().div_ceil(columns).max(1);
+        let mut tileset: Grid<u16,RenderedTextCell> = Grid::new(columns as u16, rows as u16);
+        for (i, tile) in self.tiles.iter().enumerate() {
+            tileset.set(&Point::new_usize(i % columns, i / columns), tile.clone());
+        }
+        tileset.rasterize(self.tilesize)
+    }
+
+    ///Writes the map as TMX, the tileset image is referenced by the given (relative) filename
+    pub fn write_tmx(&self, writer: &mut impl Write, tilesetimage: &str) -> io::Result<()> {
+        let columns = self.tilesetcolumns();
+        let rows = self.tiles.len().div_ceil(columns).max(1);
+        let objectcount: usize = self.objectgroups.iter().map(|group| group.objects.len()).sum();
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(writer, "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" tilewidth=\"{ts}\" tileheight=\"{ts}\" infinite=\"0\" nextlayerid=\"{}\" nextobjectid=\"{}\">",
+                 self.width, self.height, self.objectgroups.len() + 2, objectcount + 1, ts = self.tilesize)?;
+        writeln!(writer, " <tileset firstgid=\"1\" name=\"procmapgen\" tilewidth=\"{ts}\" tileheight=\"{ts}\" tilecount=\"{}\" columns=\"{}\">",
+                 self.tiles.len(), columns, ts = self.tilesize)?;
+        writeln!(writer, "  <image source=\"{}\" width=\"{}\" height=\"{}\"/>", escape(tilesetimage), columns * self.tilesize, rows * self.tilesize)?;
+        for (i, kind) in self.tilekinds.iter().enumerate() {
+            writeln!(writer, "  <tile id=\"{}\">\n   <properties>\n    <property name=\"kind\" value=\"{}\"/>\n   </properties>\n  </tile>", i, escape(kind))?;
+        }
+        writeln!(writer, " </tileset>")?;
+        writeln!(writer, " <layer id=\"1\" name=\"map\" width=\"{}\" height=\"{}\">", self.width, self.height)?;
+        writeln!(writer, "  <data encoding=\"csv\">")?;
+        for (y, row) in self.data.chunks(self.width.max(1)).enumerate() {
+            let row: Vec<String> = row.iter().map(|gid| gid.to_string()).collect();
+            //rows are separated by commas too, except for the last
+            writeln!(writer, "{}{}", row.join(","), if y + 1 < self.height { "," } else { "" })?;
+        }
+        writeln!(writer, "  </data>\n </layer>")?;
+        let mut objectid = 1;
+        for (i, group) in self.objectgroups.iter().enumerate() {
+            writeln!(writer, " <objectgroup id=\"{}\" name=\"{}\">", i + 2, escape(&group.name))?;
+            for object in group.objects.iter() {
+                if object.width == 0 && object.height == 0 {
+                    writeln!(writer, "  <object id=\"{}\" name=\"{}\" type=\"{}\" x=\"{}\" y=\"{}\">\n   <point/>\n  </object>",
+                             objectid, escape(&object.name), escape(&object.kind),
+                             object.x * self.tilesize + self.tilesize / 2, object.y * self.tilesize + self.tilesize / 2)?;
+                } else {
+                    writeln!(writer, "  <object id=\"{}\" name=\"{}\" type=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                             objectid, escape(&object.name), escape(&object.kind),
+                             object.x * self.tilesize, object.y * self.tilesize, object.width * self.tilesize, object.height * self.tilesize)?;
+                }
+                objectid += 1;
+            }
+            writeln!(writer, " </objectgroup>")?;
+        }
+        writeln!(writer, "</map>")
+    }
+
+    ///Writes the map in the JSON map format of Tiled (TMJ), with the same content as `write_tmx()`
+    pub fn write_json(&self, writer: &mut impl Write, tilesetimage: &str) -> io::Result<()> {
+        let columns = self.tilesetcolumns();
+        let rows = self.tiles.len().div_ceil(columns).max(1);
+        let objectcount: usize = self.objectgroups.iter().map(|group| group.objects.len()).sum();
+        let tiles: Vec<serde_json::Value> = self.tilekinds.iter().enumerate().map(|(i, kind)| json!({
+            "id": i,
+            "properties": [{ "name": "kind", "type": "string", "value": kind }],
+        })).collect();
+        let mut layers = vec!(json!({
+            "id": 1,
+            "name": "map",
+            "type": "tilelayer",
+            "width": self.width,
+            "height": self.height,
+            "x": 0,
+            "y": 0,
+            "opacity": 1,
+            "visible": true,
+            "data": self.data,
+        }));
+        let mut objectid = 1;
+        for (i, group) in self.objectgroups.iter().enumerate() {
+            let mut objects = Vec::new();
+            for object in group.objects.iter() {
+                let mut value = json!({
+                    "id": objectid,
+                    "name": object.name,
+                    "type": object.kind,
+                    "rotation": 0,
+                    "visible": true,
+                });
+                if object.width == 0 && object.height == 0 {
+                    value["x"] = json!(object.x * self.tilesize + self.tilesize / 2);
+                    value["y"] = json!(object.y * self.tilesize + self.tilesize / 2);
+                    value["width"] = json!(0);
+                    value["height"] = json!(0);
+                    value["point"] = json!(true);
+                } else {
+                    value["x"] = json!(object.x * self.tilesize);
+                    value["y"] = json!(object.y * self.tilesize);
+                    value["width"] = json!(object.width * self.tilesize);
+                    value["height"] = json!(object.height * self.tilesize);
+                }
+                objects.push(value);
+                objectid += 1;
+            }
+            layers.push(json!({
+                "id": i + 2,
+                "name": group.name,
+                "type": "objectgroup",
+                "draworder": "topdown",
+                "x": 0,
+                "y": 0,
+                "opacity": 1,
+                "visible": true,
+                "objects": objects,
+            }));
+        }
+        let map = json!({
+            "type": "map",
+            "version": "1.10",
+            "orientation": "orthogonal",
+            "renderorder": "right-down",
+            "width": self.width,
+            "height": self.height,
+            "tilewidth": self.tilesize,
+            "tileheight": self.tilesize,
+            "infinite": false,
+            "nextlayerid": self.objectgroups.len() + 2,
+            "nextobjectid": objectcount + 1,
+            "tilesets": [{
+                "firstgid": 1,
+                "name": "procmapgen",
+                "tilewidth": self.tilesize,
+                "tileheight": self.tilesize,
+                "tilecount": self.tiles.len(),
+                "columns": columns,
+                "margin": 0,
+                "spacing": 0,
+                "image": tilesetimage,
+                "imagewidth": columns * self.tilesize,
+                "imageheight": rows * self.tilesize,
+                "tiles": tiles,
+            }],
+            "layers": layers,
+        });
+        serde_json::to_writer_pretty(&mut *writer, &map)?;
+        writeln!(writer)
+    }
+
+    ///Saves the map as TMX, or in the JSON format for the .tmj extension. The tileset image is
+    ///written as PNG next to it (with -tiles appended to the name)
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let path = Path::new(filename);
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("map");
+        let imagefilename = format!("{}-tiles.png", stem);
+        self.tilesetimage().save(path.with_file_name(&imagefilename).to_str().expect("Invalid filename"))?;
+        let mut writer = BufWriter::new(File::create(filename)?);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmj") => self.write_json(&mut writer, &imagefilename),
+            _ => self.write_tmx(&mut writer, &imagefilename),
+        }?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_matches_tmx() {
+        let mut map = TiledMap::new(3, 2, 8);
+        let gid = map.addtile("wall", RenderedTextCell::default());
+        map.set(&Point(2u16, 1u16), gid);
+        map.objectgroups.push(ObjectGroup {
+            name: "seeds".to_string(),
+            objects: vec!(TiledObject { name: "seed 1".to_string(), kind: "backbone seed".to_string(), x: 1, y: 0, width: 0, height: 0 }),
+        });
+        let mut buffer: Vec<u8> = Vec::new();
+        map.write_json(&mut buffer, "map-tiles.png").unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(json["layers"][0]["data"], json!([0, 0, 0, 0, 0, 1]));
+        assert_eq!(json["tilesets"][0]["tiles"][0]["properties"][0]["value"], "wall");
+        let object = &json["layers"][1]["objects"][0];
+        assert_eq!((object["x"].as_u64(), object["y"].as_u64(), object["point"].as_bool()), (Some(12), Some(4), Some(true)));
+    }
+}
@@ -0,0 +1,158 @@
+use std::io::{self,Write};
+use std::time::Duration;
+use crossterm::{queue,execute,terminal,cursor};
+use crossterm::event::{self,Event,KeyCode,KeyEventKind};
+use crossterm::style::{Print,Attribute,SetAttribute};
+
+use procmapgen::grid::{Grid,GenericGrid,RenderedTextCell};
+use procmapgen::colour::ColourMode;
+use procmapgen::point::Point;
+
+///Keys understood by the viewer, shown on the status line
+const HELP: &str = "space:reseed p:previous t/T:type y:style i/I:iterations r/R:rooms b/B:backboneseeds arrows/hjkl:pan a:auto q:quit";
+
+///The parameters that can be changed while viewing
+#[derive(Debug,Clone,PartialEq)]
+pub struct ViewerSettings {
+    pub maptype: String,
+    pub style: String,
+    pub seed: u64,
+    pub iterations: usize,
+    pub rooms: usize,
+    pub backboneseeds: u16,
+}
+
+///Restores the terminal when the viewer ends, also if it ends with an error
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+///Cycles through a list, forwards or backwards, starting from the current value (or the first
+///value if the current value is not in the list)
+fn cycle(values: &[&str], current: &str, forward: bool) -> String {
+    let index = match values.iter().position(|value| *value == current) {
+        Some(index) if forward => (index + 1) % values.len(),
+        Some(index) => (index + values.len() - 1) % values.len(),
+        None => 0,
+    };
+    values[index].to_string()
+}
+
+///Shows maps in the terminal and regenerates them as the settings are changed with the keyboard.
+///`types` lists the map types with the render styles each supports, `render` generates and
+///renders a map for the given settings, its errors are shown on the status line. With `auto`, a new seed is picked every interval until
+///auto mode is toggled off. Returns the settings of the last map shown.
+pub fn view(mut settings: ViewerSettings, types: &[(&str, &[&str])], colourmode: ColourMode, interval: Duration, mut auto: bool,
+            mut render: impl FnMut(&ViewerSettings) -> Result<Grid<u16,RenderedTextCell>,String>) -> io::Result<ViewerSettings> {
+    let _guard = TerminalGuard::new()?;
+    let mut stdout = io::stdout();
+    let mut history: Vec<u64> = Vec::new();
+    let (mut left, mut top) = (0usize, 0usize);
+    let mut grid: Result<Grid<u16,RenderedTextCell>,String> = Err(String::new());
+    let mut regenerate = true;
+    loop {
+        if regenerate {
+            grid = render(&settings);
+            regenerate = false;
+        }
+
+        let (columns, rows) = terminal::size()?;
+        let (columns, rows) = (columns as usize, (rows as usize).saturating_sub(1));
+        if let Ok(grid) = grid.as_ref() {
+            left = left.min(grid.width_as_usize().saturating_sub(columns));
+            top = top.min(grid.height_as_usize().saturating_sub(rows));
+        }
+        for row in 0..rows {
+            queue!(stdout, cursor::MoveTo(0, row as u16))?;
+            if let Ok(grid) = grid.as_ref() {
+                let mut line = String::new();
+                for x in left..(left + columns).min(grid.width_as_usize()) {
+                    if let Some(cell) = grid.get(&Point::new_usize(x, top + row)) {
+                        line += &cell.paint(colourmode);
+                    }
+                }
+                queue!(stdout, Print(line))?;
+            }
+            queue!(stdout, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+        }
+        let mut status = format!(" {} | style {} | seed {} | iterations {} | rooms {} | backboneseeds {} | at {},{}{} | {}",
+                                 settings.maptype, settings.style, settings.seed, settings.iterations, settings.rooms, settings.backboneseeds,
+                                 left, top, match grid.as_ref() { Err(err) => format!(" | {}", err), Ok(_) if auto => " | auto".to_string(), Ok(_) => String::new() }, HELP);
+        status = status.chars().take(columns).collect();
+        queue!(stdout, cursor::MoveTo(0, rows as u16), SetAttribute(Attribute::Reverse), Print(format!("{:width$}", status, width = columns)), SetAttribute(Attribute::Reset))?;
+        stdout.flush()?;
+
+        if !event::poll(if auto { interval } else { Duration::from_secs(3600) })? {
+            //nothing pressed during the interval in auto mode
+            if auto {
+                history.push(settings.seed);
+                settings.seed = rand::random::<u64>();
+                regenerate = true;
+            }
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key.code,
+            _ => continue,
+        };
+        let pagewidth = (columns / 2).max(1);
+        let pageheight = (rows / 2).max(1);
+        let previoussettings = settings.clone();
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char(' ') | KeyCode::Char('n') => {
+                history.push(settings.seed);
+                settings.seed = rand::random::<u64>();
+            },
+            KeyCode::Char('p') | KeyCode::Backspace => {
+                if let Some(seed) = history.pop() {
+                    settings.seed = seed;
+                }
+            },
+            KeyCode::Char('a') => auto = !auto,
+            KeyCode::Char(c @ 't') | KeyCode::Char(c @ 'T') => {
+                let names: Vec<&str> = types.iter().map(|(name, _)| *name).collect();
+                settings.maptype = cycle(&names, &settings.maptype, c == 't');
+                let styles = types.iter().find(|(name, _)| *name == settings.maptype).map(|(_, styles)| *styles).unwrap_or(&[]);
+                if !styles.is_empty() && !styles.contains(&settings.style.as_str()) {
+                    settings.style = styles[0].to_string();
+                }
+            },
+            KeyCode::Char('y') => {
+                if let Some((_, styles)) = types.iter().find(|(name, _)| *name == settings.maptype).filter(|(_, styles)| !styles.is_empty()) {
+                    settings.style = cycle(styles, &settings.style, true);
+                }
+            },
+            KeyCode::Char('i') => settings.iterations += 10,
+            KeyCode::Char('I') => settings.iterations = settings.iterations.saturating_sub(10).max(1),
+            KeyCode::Char('r') => settings.rooms += 1,
+            KeyCode::Char('R') => settings.rooms = settings.rooms.saturating_sub(1).max(1),
+            KeyCode::Char('b') => settings.backboneseeds = settings.backboneseeds.saturating_add(1),
+            KeyCode::Char('B') => settings.backboneseeds = settings.backboneseeds.saturating_sub(1).max(1),
+            KeyCode::Left | KeyCode::Char('h') => left = left.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => left += 1,
+            KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => top += 1,
+            KeyCode::Char('H') => left = left.saturating_sub(pagewidth),
+            KeyCode::Char('L') => left += pagewidth,
+            KeyCode::PageUp | KeyCode::Char('K') => top = top.saturating_sub(pageheight),
+            KeyCode::PageDown | KeyCode::Char('J') => top += pageheight,
+            _ => {},
+        }
+        regenerate = settings != previoussettings;
+    }
+    Ok(settings)
+}
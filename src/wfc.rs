@@ -0,0 +1,473 @@
+use rand::{SeedableRng,Rng};
+use rand_pcg::Pcg32;
+use std::cmp::PartialEq;
+use num::{Integer,Num,FromPrimitive,ToPrimitive,Bounded,CheckedAdd,CheckedSub};
+use std::fs;
+
+use crate::common::Direction;
+use crate::point::Point;
+use crate::grid::{Grid,GenericGrid,RenderedTextCell};
+use crate::error::Error;
+use crate::generator::{MapGenerator,ParameterInfo,Parameters,parameter};
+
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+///A small sample town, used when no sample is provided
+pub const DEFAULT_SAMPLE: &str = "\
+..........
+.┌──┐.....
+.│▒▒│..♣♣.
+.└┬─┘..♣♣.
+..│.......
+──┼───────
+..│..┌─┐..
+..│..│▒│.♣
+..│..└┬┘..
+..└───┘...";
+
+///A set of tiles along with the rules on which tiles may be adjacent to each other
+#[derive(Debug,Clone,Default)]
+pub struct TileSet {
+    ///the glyph to render for each tile
+    pub glyphs: Vec<char>,
+    ///the relative frequency of each tile
+    pub weights: Vec<f64>,
+    ///optional foreground colour for each tile
+    pub foreground_colours: Vec<Option<(u8,u8,u8)>>,
+    ///optional background colour for each tile
+    pub background_colours: Vec<Option<(u8,u8,u8)>>,
+    ///for each tile and each direction (indexed by Direction), the tiles that may be next to it
+    adjacency: Vec<[Vec<usize>; 4]>,
+}
+
+impl TileSet {
+    pub fn new() -> TileSet {
+        TileSet::default()
+    }
+
+    ///Learns the tiles and adjacency rules from a sample, each character is a tile and each pair of
+    ///characters next to each other in the sample is allowed to be next to each other in the output.
+    ///Tiles are weighted by how often they occur.
+    pub fn from_sample(sample: &str) -> TileSet {
+        let mut tileset = TileSet::new();
+        let rows: Vec<Vec<usize>> = sample.lines().map(|line| {
+            line.chars().map(|glyph| tileset.add(glyph, 0.0)).collect()
+        }).collect();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                tileset.weights[*tile] += 1.0;
+                if let Some(east) = row.get(x+1) {
+                    tileset.allow(*tile, Direction::East, *east);
+                }
+                if let Some(south) = rows.get(y+1).and_then(|row| row.get(x)) {
+                    tileset.allow(*tile, Direction::South, *south);
+                }
+            }
+        }
+        tileset
+    }
+
+    ///Adds a tile (if it doesn't exist yet) and returns its index
+    pub fn add(&mut self, glyph: char, weight: f64) -> usize {
+        if let Some(tile) = self.tile(glyph) {
+            return tile;
+        }
+        self.glyphs.push(glyph);
+        self.weights.push(weight);
+        self.foreground_colours.push(None);
+        self.background_colours.push(None);
+        self.adjacency.push([Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+        self.glyphs.len() - 1
+    }
+
+    ///Returns the index of the tile with the specified glyph
+    pub fn tile(&self, glyph: char) -> Option<usize> {
+        self.glyphs.iter().position(|g| *g == glyph)
+    }
+
+    ///Allow tile2 to be placed in the specified direction of tile (and so tile in the opposite
+    ///direction of tile2)
+    pub fn allow(&mut self, tile: usize, direction: Direction, tile2: usize) {
+        if !self.adjacency[tile][direction as usize].contains(&tile2) {
+            self.adjacency[tile][direction as usize].push(tile2);
+        }
+        if !self.adjacency[tile2][direction.opposite() as usize].contains(&tile) {
+            self.adjacency[tile2][direction.opposite() as usize].push(tile);
+        }
+    }
+
+    ///Allow two tiles to be next to each other in all directions
+    pub fn allow_all(&mut self, tile: usize, tile2: usize) {
+        for direction in DIRECTIONS.iter() {
+            self.allow(tile, *direction, tile2);
+        }
+    }
+
+    pub fn set_colours(&mut self, tile: usize, foreground: Option<(u8,u8,u8)>, background: Option<(u8,u8,u8)>) {
+        self.foreground_colours[tile] = foreground;
+        self.background_colours[tile] = background;
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct WfcGridProperties {
+    ///the tiles and adjacency rules
+    pub tileset: TileSet,
+
+    ///give up after this many backtracks, leaving the remaining cells undetermined
+    pub maxbacktracks: usize,
+}
+
+impl WfcGridProperties {
+    ///Checks that every tile can be stored in a grid with the given value type, cells hold the
+    ///index of their tile plus one
+    pub fn validate<ValueType: FromPrimitive + ToPrimitive + Bounded>(&self) -> Result<(), Error> {
+        if ValueType::from_usize(self.tileset.len()).is_none() {
+            return Err(Error::InvalidParameter(format!("The sample has {} different tiles, at most {} are supported",
+                       self.tileset.len(), ValueType::max_value().to_usize().unwrap_or(0))));
+        }
+        Ok(())
+    }
+}
+
+///The state of the wave: which tiles are still possible in which cells. Every removal is
+///recorded so it can be undone when backtracking.
+struct Wave {
+    width: usize,
+    height: usize,
+    tiles: usize,
+    ///flattened: cell * tiles + tile
+    possible: Vec<bool>,
+    ///number of possible tiles per cell
+    counts: Vec<usize>,
+    ///all removals as (cell, tile), in order
+    trail: Vec<(usize,usize)>,
+    ///flattened: (direction * tiles + tile) * tiles + tile2
+    compatible: Vec<bool>,
+}
+
+impl Wave {
+    fn new(width: usize, height: usize, tileset: &TileSet) -> Wave {
+        let tiles = tileset.len();
+        let mut compatible = vec![false; 4 * tiles * tiles];
+        for (tile, adjacency) in tileset.adjacency.iter().enumerate() {
+            for (direction, neighbours) in adjacency.iter().enumerate() {
+                for tile2 in neighbours.iter() {
+                    compatible[(direction * tiles + tile) * tiles + tile2] = true;
+                }
+            }
+        }
+        Wave {
+            width,
+            height,
+            tiles,
+            possible: vec![true; width * height * tiles],
+            counts: vec![tiles; width * height],
+            trail: Vec::new(),
+            compatible,
+        }
+    }
+
+    fn is_possible(&self, cell: usize, tile: usize) -> bool {
+        self.possible[cell * self.tiles + tile]
+    }
+
+    fn ban(&mut self, cell: usize, tile: usize) {
+        self.possible[cell * self.tiles + tile] = false;
+        self.counts[cell] -= 1;
+        self.trail.push((cell, tile));
+    }
+
+    ///Undo all removals back to the given trail length
+    fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            let (cell, tile) = self.trail.pop().unwrap();
+            self.possible[cell * self.tiles + tile] = true;
+            self.counts[cell] += 1;
+        }
+    }
+
+    fn neighbour(&self, cell: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (cell % self.width, cell / self.width);
+        match direction {
+            Direction::North if y > 0 => Some(cell - self.width),
+            Direction::East if x + 1 < self.width => Some(cell + 1),
+            Direction::South if y + 1 < self.height => Some(cell + self.width),
+            Direction::West if x > 0 => Some(cell - 1),
+            _ => None,
+        }
+    }
+
+    ///Removes tiles from neighbouring cells that are no longer supported by any possible tile,
+    ///until nothing changes. Returns false on a contradiction (a cell without any possible tile).
+    fn propagate(&mut self, mut stack: Vec<usize>) -> bool {
+        while let Some(cell) = stack.pop() {
+            for direction in DIRECTIONS.iter() {
+                if let Some(neighbour) = self.neighbour(cell, *direction) {
+                    let mut changed = false;
+                    for tile2 in 0..self.tiles {
+                        if self.is_possible(neighbour, tile2) {
+                            let supported = (0..self.tiles).any(|tile| {
+                                self.is_possible(cell, tile) && self.compatible[(*direction as usize * self.tiles + tile) * self.tiles + tile2]
+                            });
+                            if !supported {
+                                self.ban(neighbour, tile2);
+                                changed = true;
+                            }
+                        }
+                    }
+                    if self.counts[neighbour] == 0 {
+                        return false;
+                    }
+                    if changed {
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    ///Returns the undetermined cell with the lowest entropy, ties are broken randomly
+    fn observe<R: Rng + ?Sized>(&self, rng: &mut R, weights: &[f64]) -> Option<usize> {
+        let mut minentropy: Option<f64> = None;
+        let mut selected: Option<usize> = None;
+        for cell in 0..self.counts.len() {
+            if self.counts[cell] > 1 {
+                let mut sum = 0.0;
+                let mut sumlog = 0.0;
+                for (tile, weight) in weights.iter().enumerate() {
+                    if self.is_possible(cell, tile) && *weight > 0.0 {
+                        sum += weight;
+                        sumlog += weight * weight.ln();
+                    }
+                }
+                let entropy = if sum > 0.0 { sum.ln() - sumlog / sum } else { 0.0 };
+                let entropy = entropy + rng.gen::<f64>() * 1e-6;
+                if minentropy.is_none() || entropy < minentropy.unwrap() {
+                    minentropy = Some(entropy);
+                    selected = Some(cell);
+                }
+            }
+        }
+        selected
+    }
+
+    ///Picks one of the possible tiles for the cell, proportional to the tile weights
+    fn choose<R: Rng + ?Sized>(&self, rng: &mut R, cell: usize, weights: &[f64]) -> usize {
+        let candidates: Vec<usize> = (0..self.tiles).filter(|tile| self.is_possible(cell, *tile)).collect();
+        let total: f64 = candidates.iter().map(|tile| weights[*tile]).sum();
+        if total > 0.0 {
+            let mut threshold = rng.gen::<f64>() * total;
+            for tile in candidates.iter() {
+                threshold -= weights[*tile];
+                if threshold < 0.0 {
+                    return *tile;
+                }
+            }
+        }
+        candidates[rng.gen_range(0, candidates.len())]
+    }
+
+    fn tile(&self, cell: usize) -> Option<usize> {
+        if self.counts[cell] == 1 {
+            (0..self.tiles).find(|tile| self.is_possible(cell, *tile))
+        } else {
+            None
+        }
+    }
+}
+
+pub trait WfcGrid<ScaleType, ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: WfcGridProperties) -> Result<Grid<ScaleType,ValueType>, Error>;
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: WfcGridProperties) -> Result<Grid<ScaleType,ValueType>, Error>;
+    fn render(&self, tileset: &TileSet) -> Grid<ScaleType,RenderedTextCell>;
+    fn rendercell(&self, point: &Point<ScaleType>, tileset: &TileSet) -> RenderedTextCell;
+}
+
+impl<ScaleType,ValueType> WfcGrid<ScaleType,ValueType> for Grid<ScaleType,ValueType> where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    ///Fills the grid by wave function collapse. Each cell holds the index of its tile plus one.
+    ///Fails if the grid could not be filled within the maximum number of backtracks (another seed
+    ///may work). All tiles must fit the value type, see `WfcGridProperties::validate()`.
+    fn generate(width: ScaleType, height: ScaleType, seed: u64, properties: WfcGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        Self::generate_with_rng::<Pcg32>(width, height, seed, properties)
+    }
+
+    ///Generates the grid like `generate()`, with a random number generator of the given type seeded from
+    ///`seed`
+    fn generate_with_rng<R: Rng + SeedableRng>(width: ScaleType, height: ScaleType, seed: u64, properties: WfcGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        let mut rng = R::seed_from_u64(seed);
+        let mut grid: Grid<ScaleType,ValueType> = Grid::new(width, height);
+        let tileset = properties.tileset;
+        if tileset.is_empty() {
+            return Err(Error::InvalidParameter("The sample has no tiles".to_string()));
+        }
+        let mut wave = Wave::new(grid.width_as_usize(), grid.height_as_usize(), &tileset);
+
+        //the decisions taken so far: (trail length before the decision, cell, tile)
+        let mut decisions: Vec<(usize,usize,usize)> = Vec::new();
+        let mut backtracks = 0;
+        let mut consistent = wave.propagate((0..wave.counts.len()).collect());
+        loop {
+            if !consistent {
+                //backtrack: undo the last decision and rule out the tile chosen there
+                while let Some((mark, cell, tile)) = decisions.pop() {
+                    wave.undo(mark);
+                    wave.ban(cell, tile);
+                    backtracks += 1;
+                    if wave.counts[cell] > 0 && wave.propagate(vec!(cell)) {
+                        consistent = true;
+                        break;
+                    }
+                }
+                if !consistent || backtracks > properties.maxbacktracks {
+                    break;
+                }
+            }
+            if let Some(cell) = wave.observe(&mut rng, &tileset.weights) {
+                let tile = wave.choose(&mut rng, cell, &tileset.weights);
+                decisions.push((wave.trail.len(), cell, tile));
+                for other in 0..wave.tiles {
+                    if other != tile && wave.is_possible(cell, other) {
+                        wave.ban(cell, other);
+                    }
+                }
+                consistent = wave.propagate(vec!(cell));
+            } else {
+                break;
+            }
+        }
+
+        let mut undetermined = 0;
+        for cell in 0..wave.counts.len() {
+            if let Some(tile) = wave.tile(cell) {
+                grid.set_index(cell, ValueType::from_usize(tile + 1).expect("Too many tiles for value type"));
+            } else {
+                undetermined += 1;
+            }
+        }
+        if undetermined > 0 {
+            return Err(Error::Generation(format!("Wave function collapse gave up after {} backtracks with {} of {} cells undetermined, try another seed or more backtracks",
+                       backtracks, undetermined, wave.counts.len())));
+        }
+        Ok(grid)
+    }
+
+    fn render(&self, tileset: &TileSet) -> Grid<ScaleType,RenderedTextCell> {
+        let mut renderedgrid: Grid<ScaleType, RenderedTextCell> = Grid::new(self.width(), self.height());
+        for point in self.rectangle().iter() {
+            renderedgrid.set(&point,  WfcGrid::rendercell(self, &point, tileset) );
+        }
+        renderedgrid
+    }
+
+    fn rendercell(&self, point: &Point<ScaleType>, tileset: &TileSet) -> RenderedTextCell {
+        let v = self[point].to_usize().unwrap();
+        if v == 0 || v > tileset.len() {
+            //undetermined
+            RenderedTextCell {
+                background_colour: None,
+                foreground_colour: None,
+                text: Some("?".to_string()),
+            }
+        } else {
+            RenderedTextCell {
+                background_colour: tileset.background_colours[v-1],
+                foreground_colour: tileset.foreground_colours[v-1],
+                text: Some(tileset.glyphs[v-1].to_string()),
+            }
+        }
+    }
+}
+
+///Generates maps with wave function collapse through `WfcGrid`, for the generator registry
+#[derive(Debug,Clone,Copy,Default)]
+pub struct WfcGenerator;
+
+impl<ScaleType,ValueType> MapGenerator<ScaleType,ValueType> for WfcGenerator where
+    ScaleType: Integer + FromPrimitive + ToPrimitive + Bounded +  Copy,
+    ValueType: Num + FromPrimitive + ToPrimitive + PartialOrd + PartialEq + Bounded + CheckedAdd + CheckedSub + Copy + Default {
+
+    type Properties = WfcGridProperties;
+
+    fn name(&self) -> &'static str {
+        "wfc"
+    }
+
+    fn description(&self) -> &'static str {
+        "Wave function collapse: tiles and adjacency rules are learned from a sample text"
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        vec!(
+            ParameterInfo::new("sample", "Text file with a sample to learn the tiles and adjacency rules from, each character is a tile (a built-in sample is used otherwise)", None),
+            ParameterInfo::new("backtracks", "Maximum number of backtracks before giving up", Some("1000")),
+        )
+    }
+
+    fn properties(&self, parameters: &Parameters) -> Result<WfcGridProperties, Error> {
+        let tileset = match parameters.get("sample") {
+            Some(filename) => TileSet::from_sample(&fs::read_to_string(filename).map_err(|err| Error::InvalidParameter(format!("Unable to read sample {}: {}", filename, err)))?),
+            None => TileSet::from_sample(DEFAULT_SAMPLE),
+        };
+        Ok(WfcGridProperties {
+            tileset,
+            maxbacktracks: parameter(parameters, "backtracks")?,
+        })
+    }
+
+    fn validate(&self, _width: ScaleType, _height: ScaleType, properties: &WfcGridProperties) -> Result<(), Error> {
+        properties.validate::<ValueType>()
+    }
+
+    fn generate(&self, width: ScaleType, height: ScaleType, seed: u64, properties: &WfcGridProperties) -> Result<Grid<ScaleType,ValueType>, Error> {
+        <Grid<ScaleType,ValueType> as WfcGrid<ScaleType,ValueType>>::generate(width, height, seed, properties.clone())
+    }
+
+    fn render(&self, grid: &Grid<ScaleType,ValueType>, _style: &str, properties: &WfcGridProperties) -> Grid<ScaleType,RenderedTextCell> {
+        WfcGrid::render(grid, &properties.tileset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjacency_is_allowed() {
+        let tileset = TileSet::from_sample(DEFAULT_SAMPLE);
+        let properties = WfcGridProperties { tileset: tileset.clone(), maxbacktracks: 1000 };
+        let grid = <Grid<u16,u8> as WfcGrid<u16,u8>>::generate(30, 20, 42, properties).expect("Generation failed");
+        for (point, value) in grid.iter() {
+            assert_ne!(*value, 0, "cell {} was not determined", point);
+            for direction in [Direction::East, Direction::South].iter() {
+                if let Some(neighbour) = grid.getneighbour(&point, *direction) {
+                    let (tile, tile2) = (*value as usize - 1, grid[&neighbour] as usize - 1);
+                    assert!(tileset.adjacency[tile][*direction as usize].contains(&tile2),
+                            "{} may not be {:?} of {}", tileset.glyphs[tile2], direction, tileset.glyphs[tile]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_sample_fails() {
+        //a single row has no rules for vertical neighbours, so no map with two rows can be filled
+        let properties = WfcGridProperties { tileset: TileSet::from_sample("ab\n"), maxbacktracks: 10 };
+        assert!(<Grid<u16,u8> as WfcGrid<u16,u8>>::generate(4, 3, 42, properties).is_err());
+    }
+}